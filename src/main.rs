@@ -1,5 +1,6 @@
 use std::io;
 
 fn main() -> io::Result<()> {
-    ru_shell::boot()
+    let status = ru_shell::boot()?;
+    std::process::exit(status)
 }
@@ -0,0 +1,74 @@
+/// A piece of a word, kept apart until the word is expanded right before the
+/// command runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    Literal(String),     // unquoted text, where glob characters are special
+    Quoted(String),      // quoted or escaped text, always taken literally
+    Param(String),       // $NAME, ${NAME} or $?, split into fields on whitespace
+    QuotedParam(String), // a parameter inside double quotes, always one field
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word(pub Vec<WordPart>);
+
+/// What a redirection does with its target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RedirectKind {
+    Read,           // [n]<file
+    Write,          // [n]>file
+    Append,         // [n]>>file
+    Duplicate,      // [n]>&m, or [n]>&- to close n
+    DuplicateInput, // [n]<&m, or [n]<&- to close n
+    WriteBoth,      // &>file
+    AppendBoth,     // &>>file
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub fd: i32,
+    pub kind: RedirectKind,
+    pub target: Word,
+}
+
+/// A command name with its arguments, plus the variable assignments written
+/// before it and the redirections to apply while it runs
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<(String, Word)>,
+    pub args: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl SimpleCommand {
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty() && self.args.is_empty() && self.redirects.is_empty()
+    }
+}
+
+/// Commands joined by `|`, each one's stdout feeding the next one's stdin
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pipeline {
+    pub commands: Vec<SimpleCommand>,
+    pub text: String, // the pipeline as written, shown by `jobs`
+}
+
+/// How a pipeline in a command list depends on the one before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListOp {
+    Always,    // first pipeline or after `;` or `&`
+    IfSuccess, // after `&&`
+    IfFailure, // after `||`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub op: ListOp,
+    pub pipeline: Pipeline,
+    pub background: bool, // followed by `&`
+}
+
+/// A whole command line: pipelines joined by `;`, `&`, `&&` and `||`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandList {
+    pub items: Vec<ListItem>,
+}
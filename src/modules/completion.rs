@@ -0,0 +1,332 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use rustyline::completion::Pair;
+
+use crate::{
+    exec::BUILTINS,
+    features::{complete::CompletionSpec, external::is_executable, ls::LS_OPTIONS},
+    glob::matches,
+    parse,
+    shell::Shell,
+};
+
+/// Where the word under the cursor sits in its command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    /// The command name
+    Command,
+    /// An argument to the named command
+    Argument(String),
+    /// The file after `<` or `>`
+    RedirectTarget,
+}
+
+/// The word being completed, from the text before the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialWord {
+    /// Byte offset where the word starts, quotes included
+    pub start: usize,
+    /// The word with quotes and escapes removed
+    pub text: String,
+    pub position: Position,
+    /// The argument before this one, or the command name for the first
+    pub previous: Option<String>,
+}
+
+/// Find the word the cursor is in, following the lexer's quoting rules closely
+/// enough to tell words and operators apart in an unfinished line
+pub fn partial_word(line: &str) -> PartialWord {
+    let mut start = 0;
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    // the command's words so far, with leading assignments left out
+    let mut words: Vec<String> = Vec::new();
+    let mut after_redirect = false;
+
+    for (i, c) in line.char_indices() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('"') if c == '\\' => escaped = true,
+            Some(_) => current.push(c),
+            None => match c {
+                '\\' => escaped = true,
+                '\'' | '"' => quote = Some(c),
+                c if c.is_whitespace() || "|;&<>".contains(c) => {
+                    let word = std::mem::take(&mut current);
+                    // the digits of `2>` name a descriptor, not a word
+                    let is_fd = "<>".contains(c) && !word.is_empty() && word.chars().all(|c| c.is_ascii_digit());
+                    if !word.is_empty() && !is_fd {
+                        if after_redirect {
+                            after_redirect = false;
+                        } else if !(words.is_empty() && is_assignment(&word)) {
+                            words.push(word);
+                        }
+                    }
+                    match c {
+                        '|' | ';' | '&' => {
+                            words.clear();
+                            after_redirect = false;
+                        }
+                        '<' | '>' => after_redirect = true,
+                        _ => {}
+                    }
+                    start = i + c.len_utf8();
+                }
+                _ => current.push(c),
+            },
+        }
+    }
+
+    let position = if after_redirect {
+        Position::RedirectTarget
+    } else {
+        match words.first() {
+            Some(command) => Position::Argument(command.clone()),
+            None => Position::Command,
+        }
+    };
+    let previous = if after_redirect { None } else { words.pop() };
+    PartialWord { start, text: current, position, previous }
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| crate::util::is_valid_name(name))
+}
+
+/// The options a built-in understands
+fn builtin_options(command: &str) -> &'static [&'static str] {
+    match command {
+        "ls" => LS_OPTIONS,
+        "cd" => &["-L", "-P"],
+        "cp" | "mv" | "rm" => &["-r", "-R", "--recursive"],
+        "shopt" => &["-s", "-u"],
+        "jobs" => &["-l", "-p"],
+        "dirs" => &["-c", "-p", "-v"],
+        "z" | "j" => &["-l"],
+        "complete" => &["-C", "-G", "-W", "-d", "-f", "-p", "-r"],
+        _ => &[],
+    }
+}
+
+/// Backslash the characters the lexer would otherwise treat specially
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_whitespace() || "\\'\"|&;<>$*?[".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Shell {
+    /// Completions for the word before `pos`: the offset the replacements
+    /// start at, and the candidates. A lone candidate that completes a whole
+    /// word is followed by a space so the next argument can be typed at once
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let word = partial_word(&line[..pos]);
+        let mut candidates = match &word.position {
+            Position::Command if !word.text.contains('/') => self.complete_command(&word.text),
+            Position::Command => self.complete_path(&word.text, |path| path.is_dir() || is_executable(path)),
+            Position::Argument(command) if self.completions.contains_key(command) => {
+                self.complete_registered(&self.completions[command], command, &word, line, pos)
+            }
+            Position::Argument(command) if word.text.starts_with('-') && !builtin_options(command).is_empty() => {
+                builtin_options(command)
+                    .iter()
+                    .filter(|option| option.starts_with(&word.text))
+                    .map(|option| candidate(option, option.to_string()))
+                    .collect()
+            }
+            Position::Argument(_) | Position::RedirectTarget => self.complete_path(&word.text, |_| true),
+        };
+
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.replacement == b.replacement);
+        if let [only] = candidates.as_mut_slice()
+            && !only.replacement.ends_with('/')
+        {
+            only.replacement.push(' ');
+        }
+        (word.start, candidates)
+    }
+
+    /// Completions for a command registered with the complete built-in. The
+    /// argument of an option with its own glob is a matching file; any other
+    /// argument may be any of the spec's words, command output and files
+    fn complete_registered(&self, spec: &CompletionSpec, command: &str, word: &PartialWord, line: &str, pos: usize) -> Vec<Pair> {
+        let option_glob = spec
+            .globs
+            .iter()
+            .find(|(option, _)| option.is_some() && *option == word.previous)
+            .map(|(_, pattern)| pattern);
+        if let Some(pattern) = option_glob {
+            return self.complete_path(&word.text, |path| path.is_dir() || file_name_matches(pattern, path));
+        }
+
+        let mut words: Vec<String> = spec.words.clone();
+        if let Some(generator) = &spec.command {
+            words.extend(self.run_completion_command(generator, command, word, line, pos));
+        }
+        let mut candidates: Vec<Pair> = words
+            .iter()
+            .filter(|candidate_word| candidate_word.starts_with(&word.text))
+            .map(|candidate_word| candidate(candidate_word, escape(candidate_word)))
+            .collect();
+
+        let patterns: Vec<&String> = spec.globs.iter().filter(|(option, _)| option.is_none()).map(|(_, p)| p).collect();
+        if spec.files || spec.dirs || !patterns.is_empty() {
+            candidates.extend(self.complete_path(&word.text, |path| {
+                path.is_dir() || spec.files || patterns.iter().any(|pattern| file_name_matches(pattern, path))
+            }));
+        }
+        candidates
+    }
+
+    /// Run a `complete -C` command the way bash does: with the command name,
+    /// the word being completed and the word before it as extra arguments, and
+    /// the line and cursor in COMP_LINE and COMP_POINT. Each line it prints is
+    /// a candidate
+    fn run_completion_command(&self, generator: &str, command: &str, word: &PartialWord, line: &str, pos: usize) -> Vec<String> {
+        let Some(args) = parse(generator)
+            .ok()
+            .and_then(|list| list.items.into_iter().next())
+            .and_then(|item| item.pipeline.commands.into_iter().next())
+            .and_then(|simple| self.expand_args(&simple.args).ok())
+        else {
+            return Vec::new();
+        };
+        let Some(program) = args.first().and_then(|name| self.resolve_program(name)) else {
+            return Vec::new();
+        };
+
+        let env = self.exported_env();
+        let output = Command::new(program)
+            .args(&args[1..])
+            .args([command, &word.text, word.previous.as_deref().unwrap_or_default()])
+            .current_dir(&self.abs_cwd)
+            .env_clear()
+            .envs(env.iter().filter_map(|var| var.split_once('=')))
+            .env("COMP_LINE", line)
+            .env("COMP_POINT", pos.to_string())
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Built-ins and the executables in the $PATH directories
+    fn complete_command(&self, prefix: &str) -> Vec<Pair> {
+        let mut candidates: Vec<Pair> = BUILTINS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| candidate(name, escape(name)))
+            .collect();
+
+        let path_var = self.get_var("PATH").unwrap_or_default();
+        for dir in path_var.split(':').filter(|dir| !dir.is_empty()) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with(prefix) && is_executable(&entry.path()) {
+                    candidates.push(candidate(&name, escape(&name)));
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Entries of the directory named by the word so far, relative to the
+    /// shell's directory, whose names start with the word's last component.
+    /// Directories end in `/`; hidden entries only show for a leading `.`
+    fn complete_path(&self, text: &str, keep: impl Fn(&Path) -> bool) -> Vec<Pair> {
+        // `~` alone completes to the home directory itself
+        if text == "~" {
+            return vec![candidate("~/", "~/".to_string())];
+        }
+        let (dir, prefix) = match text.rfind('/') {
+            Some(slash) => (&text[..=slash], &text[slash + 1..]),
+            None => ("", text),
+        };
+        let Ok(entries) = fs::read_dir(self.resolve_path(if dir.is_empty() { "." } else { dir })) else {
+            return Vec::new();
+        };
+
+        // a leading `~` is kept as typed rather than escaped
+        let shown_dir = match dir.strip_prefix('~') {
+            Some(rest) => format!("~{}", escape(rest)),
+            None => escape(dir),
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                    return None;
+                }
+                let path = entry.path();
+                if !keep(&path) {
+                    return None;
+                }
+                let suffix = if path.is_dir() { "/" } else { "" };
+                Some(candidate(&format!("{}{}", name, suffix), format!("{}{}{}", shown_dir, escape(&name), suffix)))
+            })
+            .collect()
+    }
+}
+
+fn file_name_matches(pattern: &str, path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| matches(pattern, name))
+}
+
+fn candidate(display: &str, replacement: String) -> Pair {
+    Pair { display: display.to_string(), replacement }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_word_and_its_position() {
+        let word = partial_word("A=1 ls -");
+        assert_eq!((word.start, word.text.as_str()), (7, "-"));
+        assert_eq!(word.position, Position::Argument("ls".to_string()));
+        assert_eq!(word.previous.as_deref(), Some("ls"));
+
+        let word = partial_word("cat file | gr");
+        assert_eq!((word.start, word.position), (11, Position::Command));
+
+        let word = partial_word("echo hi 2>");
+        assert_eq!((word.start, word.position), (10, Position::RedirectTarget));
+    }
+
+    #[test]
+    fn quotes_and_escapes_are_part_of_the_word() {
+        let word = partial_word("cat \"my fi");
+        assert_eq!((word.start, word.text.as_str()), (4, "my fi"));
+
+        let word = partial_word("cat my\\ fi");
+        assert_eq!((word.start, word.text.as_str()), (4, "my fi"));
+        assert_eq!(escape("my file"), "my\\ file");
+    }
+}
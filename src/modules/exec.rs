@@ -0,0 +1,281 @@
+use std::io;
+
+use crate::{
+    ast::{CommandList, ListOp, Pipeline, SimpleCommand},
+    external::{ExecArgs, to_cstring},
+    jobs::Job,
+    redirect::SavedFds,
+    shell::Shell,
+};
+
+/// How a built-in is run: with the shell, the name it was called by and its
+/// arguments, returning its exit status
+pub type Builtin = fn(&mut Shell, &str, Vec<String>) -> i32;
+
+/// Commands handled by the shell itself rather than looked up on $PATH. The
+/// dispatcher, completion and highlighting all go by this one table
+pub const BUILTINS: &[(&str, Builtin)] = &[
+    ("exit", |shell, _, args| shell.handle_exit_command(args)),
+    ("echo", |shell, _, args| shell.handle_echo_command(args)),
+    ("cd", |shell, _, args| shell.handle_cd_command(args)),
+    ("pwd", |shell, _, _| {
+        shell.pwd();
+        0
+    }),
+    ("ls", |shell, _, args| shell.handle_ls_command(args)),
+    ("cat", |shell, _, args| shell.handle_cat_command(args)),
+    ("cp", |shell, _, args| shell.handle_copy_command(args)),
+    ("mv", |shell, _, args| shell.handle_move_command(args)),
+    ("mkdir", |shell, _, args| shell.handle_mkdir_command(args)),
+    ("rm", |shell, _, args| shell.handle_rm_command(args)),
+    ("clear", |shell, _, _| {
+        shell.clear();
+        0
+    }),
+    ("export", |shell, _, args| shell.handle_export_command(args)),
+    ("unset", |shell, _, args| shell.handle_unset_command(args)),
+    ("shopt", |shell, _, args| shell.handle_shopt_command(args)),
+    ("jobs", |shell, _, args| shell.handle_jobs_command(args)),
+    ("fg", |shell, _, args| shell.handle_fg_command(args)),
+    ("bg", |shell, _, args| shell.handle_bg_command(args)),
+    ("pushd", |shell, _, args| shell.handle_pushd_command(args)),
+    ("popd", |shell, _, args| shell.handle_popd_command(args)),
+    ("dirs", |shell, _, args| shell.handle_dirs_command(args)),
+    ("z", |shell, name, args| shell.handle_jump_command(name, args)),
+    ("j", |shell, name, args| shell.handle_jump_command(name, args)),
+    ("complete", |shell, _, args| shell.handle_complete_command(args)),
+];
+
+fn find_builtin(command: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|(name, _)| *name == command).map(|(_, run)| *run)
+}
+
+pub fn is_builtin(command: &str) -> bool {
+    find_builtin(command).is_some()
+}
+
+impl Shell {
+    /// Run a command list, skipping pipelines whose `&&` / `||` condition does
+    /// not hold, and return the status of the last pipeline that ran. Pipelines
+    /// followed by `&` are started as background jobs and count as successful
+    pub fn run_list(&mut self, list: CommandList) -> i32 {
+        for item in list.items {
+            let skip = match item.op {
+                ListOp::Always => false,
+                ListOp::IfSuccess => self.last_status != 0,
+                ListOp::IfFailure => self.last_status == 0,
+            };
+            if skip {
+                continue;
+            }
+
+            self.last_status = if item.background || item.pipeline.commands.len() > 1 {
+                self.run_pipeline(item.pipeline, item.background)
+            } else {
+                let command = item.pipeline.commands.into_iter().next().unwrap_or_default();
+                self.run_command(command)
+            };
+
+            if self.should_exit {
+                break;
+            }
+        }
+        self.last_status
+    }
+
+    /// Run a single command, either a built-in or an external program, and
+    /// return its exit status
+    pub fn execute(&mut self, command: &str, args: Vec<String>) -> i32 {
+        match find_builtin(command) {
+            Some(run) => run(self, command, args),
+            None => self.run_external(command, &args),
+        }
+    }
+
+    /// Run a command with its redirections applied for the duration of the command
+    pub fn run_command(&mut self, command: SimpleCommand) -> i32 {
+        let mut args = match self.expand_args(&command.args) {
+            Ok(args) => args,
+            Err(e) => {
+                self.error(&e, true);
+                return 1;
+            }
+        };
+
+        let saved = SavedFds::save(&command.redirects);
+        if let Err(e) = self.apply_redirects(&command.redirects) {
+            self.error(&e, true);
+            saved.restore();
+            return 1;
+        }
+
+        let assignments = self.expand_assignments(&command);
+        let status = if args.is_empty() {
+            // bare assignments set shell variables
+            for (name, value) in assignments {
+                self.set_var(&name, &value);
+            }
+            0
+        } else {
+            // assignments before a command only apply to that command
+            let saved_vars = self.set_temporary_vars(assignments);
+            let name = args.remove(0);
+            let status = self.execute(&name, args);
+            self.restore_variables(saved_vars);
+            status
+        };
+
+        saved.restore();
+        status
+    }
+
+    /// Run each stage in its own process with stdout of one stage connected
+    /// to stdin of the next, all in one job. A foreground job is waited for
+    /// and its status is the status of its last stage
+    pub fn run_pipeline(&mut self, pipeline: Pipeline, background: bool) -> i32 {
+        let stages = pipeline.commands;
+        // expand every stage up front so a failed glob stops the whole pipeline
+        let mut expanded_args = Vec::with_capacity(stages.len());
+        for stage in &stages {
+            match self.expand_args(&stage.args) {
+                Ok(args) => expanded_args.push(args),
+                Err(e) => {
+                    self.error(&e, true);
+                    return 1;
+                }
+            }
+        }
+
+        let mut pids = Vec::with_capacity(stages.len());
+        let mut pgid = 0;
+        let mut prev_read: Option<i32> = None;
+
+        for (i, (stage, mut args)) in stages.iter().zip(expanded_args).enumerate() {
+            let is_last = i == stages.len() - 1;
+            let assignments = self.expand_assignments(stage);
+            let command = if args.is_empty() { String::new() } else { args.remove(0) };
+            let command = command.as_str();
+
+            let pipe_fds = if is_last {
+                None
+            } else {
+                match create_pipe() {
+                    Ok(fds) => Some(fds),
+                    Err(e) => {
+                        self.error(&format!("pipe: {}", e), true);
+                        break;
+                    }
+                }
+            };
+
+            // each stage runs in its own process, so its assignments never leak back
+            let saved_vars = self.set_temporary_vars(assignments);
+
+            // prepare the exec arguments before forking so the child only has to exec
+            let exec_args = if command.is_empty() || is_builtin(command) {
+                None
+            } else {
+                match self.resolve_program(command) {
+                    Some(program) => ExecArgs::new(command, &program, &args, &self.exported_env()).ok(),
+                    None => None,
+                }
+            };
+
+            let pid = unsafe { libc::fork() };
+            if pid < 0 {
+                self.error(&format!("fork: {}", io::Error::last_os_error()), true);
+                self.restore_variables(saved_vars);
+                break;
+            }
+
+            if pid == 0 {
+                self.enter_job(pgid, !background);
+                unsafe {
+                    if let Some(fd) = prev_read {
+                        libc::dup2(fd, libc::STDIN_FILENO);
+                        libc::close(fd);
+                    }
+                    if let Some((read_fd, write_fd)) = pipe_fds {
+                        libc::close(read_fd);
+                        libc::dup2(write_fd, libc::STDOUT_FILENO);
+                        libc::close(write_fd);
+                    }
+                }
+                if let Err(e) = self.apply_redirects(&stage.redirects) {
+                    self.error(&e, true);
+                    unsafe { libc::_exit(1) };
+                }
+                self.run_stage(command, &args, exec_args);
+            }
+
+            // parent: the pipe ends now belong to the children
+            pgid = self.add_to_job(pid, pgid);
+            self.restore_variables(saved_vars);
+            unsafe {
+                if let Some(fd) = prev_read {
+                    libc::close(fd);
+                }
+                if let Some((read_fd, write_fd)) = pipe_fds {
+                    libc::close(write_fd);
+                    prev_read = Some(read_fd);
+                }
+            }
+            pids.push(pid);
+        }
+
+        if let Some(fd) = prev_read {
+            unsafe { libc::close(fd) };
+        }
+
+        if pids.is_empty() {
+            return 1;
+        }
+        let job = Job::new(self.job_pgid(pgid), pids, pipeline.text);
+        if background {
+            self.add_background_job(job);
+            0
+        } else {
+            self.wait_foreground(job)
+        }
+    }
+
+    fn expand_assignments(&self, command: &SimpleCommand) -> Vec<(String, String)> {
+        command
+            .assignments
+            .iter()
+            .map(|(name, value)| (name.clone(), self.expand_word(value)))
+            .collect()
+    }
+
+    /// Body of a forked pipeline stage, never returning
+    fn run_stage(&mut self, command: &str, args: &[String], exec_args: Option<ExecArgs>) -> ! {
+        if let Ok(c_cwd) = to_cstring(&self.abs_cwd) {
+            unsafe { libc::chdir(c_cwd.as_ptr()) };
+        }
+
+        if command.is_empty() {
+            unsafe { libc::_exit(0) };
+        }
+
+        if is_builtin(command) {
+            let status = self.execute(command, args.to_vec());
+            unsafe { libc::_exit(status) };
+        }
+
+        match exec_args {
+            Some(exec_args) => self.exec_external(command, &exec_args),
+            None => {
+                self.error(&format!("{}: command not found", command), false);
+                unsafe { libc::_exit(127) }
+            }
+        }
+    }
+}
+
+fn create_pipe() -> io::Result<(i32, i32)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((fds[0], fds[1]))
+}
@@ -0,0 +1,123 @@
+use crate::{
+    ast::{Word, WordPart},
+    glob::{glob, has_glob_chars},
+    shell::Shell,
+};
+
+/// A field being built from the parts of a word: its literal text, and the
+/// same text as a glob pattern where quoted characters are escaped
+#[derive(Default)]
+struct Field {
+    text: String,
+    pattern: String,
+}
+
+impl Field {
+    fn push_unquoted(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.pattern.push_str(text);
+    }
+
+    fn push_quoted(&mut self, text: &str) {
+        self.text.push_str(text);
+        for c in text.chars() {
+            if matches!(c, '*' | '?' | '[' | '\\') {
+                self.pattern.push('\\');
+            }
+            self.pattern.push(c);
+        }
+    }
+}
+
+impl Shell {
+    /// Expand every word of a command into the arguments it will be run with.
+    /// Unquoted parameters are split into separate arguments on whitespace,
+    /// words that expand to nothing at all are dropped, and unquoted glob
+    /// patterns are replaced by the sorted paths they match
+    pub fn expand_args(&self, words: &[Word]) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        for word in words {
+            for field in self.expand_fields(word) {
+                if !has_glob_chars(&field.pattern) {
+                    args.push(field.text);
+                    continue;
+                }
+
+                let matches = glob(&field.pattern, &self.abs_cwd);
+                if !matches.is_empty() {
+                    args.extend(matches);
+                } else if self.options.failglob {
+                    return Err(format!("no match: {}", field.text));
+                } else if !self.options.nullglob {
+                    // like other shells, a pattern without matches is left as it is
+                    args.push(field.text);
+                }
+            }
+        }
+        Ok(args)
+    }
+
+    /// Substitute the parameters of a word and join its parts into one string,
+    /// as used for assignment values and redirection targets
+    pub fn expand_word(&self, word: &Word) -> String {
+        let mut expanded = String::new();
+        for part in &word.0 {
+            match part {
+                WordPart::Literal(text) | WordPart::Quoted(text) => expanded.push_str(text),
+                WordPart::Param(name) | WordPart::QuotedParam(name) => {
+                    expanded.push_str(&self.param_value(name))
+                }
+            }
+        }
+        expanded
+    }
+
+    fn expand_fields(&self, word: &Word) -> Vec<Field> {
+        let mut fields = Vec::new();
+        let mut current = Field::default();
+        // a field exists once anything other than an unquoted parameter contributed to it
+        let mut has_field = false;
+
+        for part in &word.0 {
+            match part {
+                WordPart::Literal(text) => {
+                    current.push_unquoted(text);
+                    has_field = true;
+                }
+                WordPart::Quoted(text) => {
+                    current.push_quoted(text);
+                    has_field = true;
+                }
+                WordPart::QuotedParam(name) => {
+                    current.push_quoted(&self.param_value(name));
+                    has_field = true;
+                }
+                WordPart::Param(name) => {
+                    let value = self.param_value(name);
+                    for (i, piece) in value.split(char::is_whitespace).enumerate() {
+                        if i > 0 && has_field {
+                            fields.push(std::mem::take(&mut current));
+                            has_field = false;
+                        }
+                        if !piece.is_empty() {
+                            current.push_unquoted(piece);
+                            has_field = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if has_field {
+            fields.push(current);
+        }
+        fields
+    }
+
+    fn param_value(&self, name: &str) -> String {
+        match name {
+            "?" => self.last_status.to_string(),
+            _ => self.get_var(name).unwrap_or_default().to_string(),
+        }
+    }
+}
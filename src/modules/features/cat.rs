@@ -1,35 +1,63 @@
-use std::{fs, path::Path};
+use std::{
+    fs::{self, File},
+    io::Read,
+    mem::ManuallyDrop,
+    os::fd::FromRawFd,
+};
 
 use crate::shell::Shell;
 
-use super::echo::echoln;
+use super::echo::write_all_fd;
 
 impl Shell {
-    pub fn handle_cat_command(&self, args: Vec<String>) {
+    pub fn handle_cat_command(&self, args: Vec<String>) -> i32 {
         if args.is_empty() {
-            self.error("cat: missing file", false);
-            return;
+            // with no files, copy stdin through (e.g. the end of a pipeline).
+            // fd 0 is read directly since std's stdin buffer may hold input meant for the shell
+            let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(libc::STDIN_FILENO) });
+            let mut content = Vec::new();
+            if let Err(e) = stdin.read_to_end(&mut content) {
+                self.error(format!("cat: -: {}", e).as_str(), false);
+                return 1;
+            }
+            return self.write_out(&content);
         }
 
-        for arg in args {
-            let file_name_str = if arg.starts_with('/') {
-                arg.clone()
-            } else if arg.starts_with("~") {
-                arg.replace("~", &self.home_dir)
-            } else {
-                format!("{}/{}", self.abs_cwd, arg)
-            };
+        let mut status = 0;
 
-            let path = Path::new(&file_name_str);
+        for arg in args {
+            let path = self.resolve_path(&arg);
             if !path.exists() {
                 self.error(format!("cat: {}: No such file or directory", arg).as_str(), false);
+                status = 1;
                 continue;
             }
 
-            match fs::read_to_string(path) {
-                Ok(content) => echoln(content.as_str()),
-                Err(e) => self.error(format!("cat: {}: {}", arg, e).as_str(), false),
+            match fs::read(&path) {
+                Ok(mut content) => {
+                    content.push(b'\n');
+                    if self.write_out(&content) != 0 {
+                        return 1;
+                    }
+                }
+                Err(e) => {
+                    self.error(format!("cat: {}: {}", arg, e).as_str(), false);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Write to stdout, reporting a failure such as a closed pipe or a full
+    /// disk. Returns the status cat should exit with
+    fn write_out(&self, content: &[u8]) -> i32 {
+        match write_all_fd(libc::STDOUT_FILENO, content) {
+            Ok(()) => 0,
+            Err(e) => {
+                self.error(&format!("cat: write error: {}", e), false);
+                1
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,140 @@
+use crate::shell::Shell;
+
+use super::echo::{echoln, echoln_err};
+
+/// How the arguments of a command are completed, as registered with the
+/// complete built-in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompletionSpec {
+    pub words: Vec<String>,      // -W: a fixed list of words
+    pub command: Option<String>, // -C: a command printing one candidate per line
+    pub files: bool,             // -f: file names
+    pub dirs: bool,              // -d: directory names
+    // -G: file names matching a glob, either for every argument or only for
+    // the one after a given option
+    pub globs: Vec<(Option<String>, String)>,
+}
+
+impl CompletionSpec {
+    /// The complete command that registers this spec again, for `complete -p`
+    fn to_command(&self, name: &str) -> String {
+        let mut command = String::from("complete");
+        if self.files {
+            command.push_str(" -f");
+        }
+        if self.dirs {
+            command.push_str(" -d");
+        }
+        if !self.words.is_empty() {
+            command.push_str(&format!(" -W {}", quote(&self.words.join(" "))));
+        }
+        if let Some(cmd) = &self.command {
+            command.push_str(&format!(" -C {}", quote(cmd)));
+        }
+        for (option, pattern) in &self.globs {
+            let glob = match option {
+                Some(option) => format!("{}={}", option, pattern),
+                None => pattern.clone(),
+            };
+            command.push_str(&format!(" -G {}", quote(&glob)));
+        }
+        format!("{} {}", command, quote(name))
+    }
+}
+
+/// Single-quote a word unless it is made only of characters that are safe
+/// unquoted
+fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+const USAGE: &str = "complete: usage: complete [-pr] [-df] [-W wordlist] [-C command] [-G [option=]glob] [name ...]";
+
+impl Shell {
+    /// `complete [-df] [-W wordlist] [-C command] [-G [option=]glob] name...`
+    /// sets how the arguments of the named commands are completed, `-p` prints
+    /// the registrations as complete commands that can go in the rc file, and
+    /// `-r` removes them
+    pub fn handle_complete_command(&mut self, args: Vec<String>) -> i32 {
+        let mut spec = CompletionSpec::default();
+        let mut print = false;
+        let mut remove = false;
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next_if(|arg| arg.starts_with('-') && arg.len() > 1) {
+            if arg == "--" {
+                break;
+            }
+            let mut flags = arg[1..].chars();
+            while let Some(flag) = flags.next() {
+                match flag {
+                    'p' => print = true,
+                    'r' => remove = true,
+                    'f' => spec.files = true,
+                    'd' => spec.dirs = true,
+                    'W' | 'C' | 'G' => {
+                        // the value is the rest of this argument or the next one
+                        let rest: String = flags.by_ref().collect();
+                        let Some(value) = Some(rest).filter(|rest| !rest.is_empty()).or_else(|| args.next()) else {
+                            self.error(&format!("complete: -{}: option requires an argument", flag), true);
+                            echoln_err(USAGE);
+                            return 2;
+                        };
+                        match flag {
+                            'W' => spec.words.extend(value.split_whitespace().map(str::to_string)),
+                            'C' => spec.command = Some(value),
+                            _ => spec.globs.push(match value.split_once('=') {
+                                Some((option, pattern)) if option.starts_with('-') => {
+                                    (Some(option.to_string()), pattern.to_string())
+                                }
+                                _ => (None, value),
+                            }),
+                        }
+                    }
+                    _ => {
+                        self.error(&format!("complete: -{}: invalid option", flag), true);
+                        echoln_err(USAGE);
+                        return 2;
+                    }
+                }
+            }
+        }
+        let names: Vec<String> = args.collect();
+
+        if remove {
+            if names.is_empty() {
+                self.completions.clear();
+            }
+            for name in &names {
+                self.completions.remove(name);
+            }
+            return 0;
+        }
+
+        if print || names.is_empty() {
+            let mut status = 0;
+            let mut shown: Vec<&String> = if names.is_empty() { self.completions.keys().collect() } else { names.iter().collect() };
+            shown.sort();
+            for name in shown {
+                match self.completions.get(name) {
+                    Some(spec) => echoln(&spec.to_command(name)),
+                    None => {
+                        self.error(&format!("complete: {}: no completion specification", name), true);
+                        status = 1;
+                    }
+                }
+            }
+            return status;
+        }
+
+        for name in names {
+            self.completions.insert(name, spec.clone());
+        }
+        0
+    }
+}
@@ -0,0 +1,168 @@
+use crate::shell::Shell;
+
+use super::echo::{echoln, echoln_err};
+
+impl Shell {
+    /// The whole directory stack, starting with the current directory
+    fn full_stack(&self) -> Vec<String> {
+        std::iter::once(self.abs_cwd.clone()).chain(self.dir_stack.iter().cloned()).collect()
+    }
+
+    /// Position in the full stack named by `+N` (from the top) or `-N` (from
+    /// the bottom), or None if the argument is not of that form
+    fn stack_index(&self, builtin: &str, arg: &str) -> Option<Result<usize, String>> {
+        let (from_top, digits) = match arg.split_at_checked(1)? {
+            ("+", digits) => (true, digits),
+            ("-", digits) => (false, digits),
+            _ => return None,
+        };
+        let n: usize = digits.parse().ok()?;
+
+        let len = self.dir_stack.len() + 1;
+        if n >= len {
+            return Some(Err(format!("{}: {}: directory stack index out of range", builtin, arg)));
+        }
+        Some(Ok(if from_top { n } else { len - 1 - n }))
+    }
+
+    /// cd for the stack built-ins, reporting the error itself
+    fn stack_cd(&mut self, dir: &str) -> bool {
+        match self.cd(dir, false) {
+            Ok(()) => true,
+            Err(e) => {
+                self.error(&e, true);
+                false
+            }
+        }
+    }
+
+    /// The stack as shown to the user, with home abbreviated to `~`
+    fn shown_stack(&self) -> Vec<String> {
+        let mut entries = vec![self.current_dir.clone()];
+        entries.extend(self.dir_stack.iter().map(|dir| self.tilde_path(dir)));
+        entries
+    }
+
+    fn print_stack(&self) {
+        echoln(&self.shown_stack().join(" "));
+    }
+
+    pub fn handle_pushd_command(&mut self, args: Vec<String>) -> i32 {
+        if args.len() > 1 {
+            self.error("pushd: too many arguments", true);
+            return 1;
+        }
+
+        match args.first() {
+            // without arguments, swap the top two directories
+            None => {
+                let Some(next) = self.dir_stack.first().cloned() else {
+                    self.error("pushd: no other directory", true);
+                    return 1;
+                };
+                let previous = self.abs_cwd.clone();
+                if !self.stack_cd(&next) {
+                    return 1;
+                }
+                self.dir_stack[0] = previous;
+            }
+            Some(arg) => match self.stack_index("pushd", arg) {
+                // +N / -N rotate the stack so that entry comes to the top
+                Some(Ok(index)) => {
+                    let mut stack = self.full_stack();
+                    stack.rotate_left(index);
+                    if !self.stack_cd(&stack[0]) {
+                        return 1;
+                    }
+                    self.dir_stack = stack.split_off(1);
+                }
+                Some(Err(e)) => {
+                    self.error(&e, true);
+                    return 1;
+                }
+                None => {
+                    let previous = self.abs_cwd.clone();
+                    if !self.stack_cd(arg) {
+                        return 1;
+                    }
+                    self.dir_stack.insert(0, previous);
+                }
+            },
+        }
+
+        self.print_stack();
+        0
+    }
+
+    pub fn handle_popd_command(&mut self, args: Vec<String>) -> i32 {
+        if args.len() > 1 {
+            self.error("popd: too many arguments", true);
+            return 1;
+        }
+        if self.dir_stack.is_empty() {
+            self.error("popd: directory stack empty", true);
+            return 1;
+        }
+
+        let index = match args.first().map(|arg| (arg, self.stack_index("popd", arg))) {
+            None => 0,
+            Some((_, Some(Ok(index)))) => index,
+            Some((_, Some(Err(e)))) => {
+                self.error(&e, true);
+                return 1;
+            }
+            Some((arg, None)) => {
+                self.error(&format!("popd: {}: invalid argument", arg), true);
+                echoln_err("popd: usage: popd [+N | -N]");
+                return 2;
+            }
+        };
+
+        if index == 0 {
+            // dropping the current directory moves to the next one
+            let next = self.dir_stack[0].clone();
+            if !self.stack_cd(&next) {
+                return 1;
+            }
+            self.dir_stack.remove(0);
+        } else {
+            self.dir_stack.remove(index - 1);
+        }
+
+        self.print_stack();
+        0
+    }
+
+    pub fn handle_dirs_command(&mut self, args: Vec<String>) -> i32 {
+        let mut verbose = false;
+        let mut per_line = false;
+        for arg in &args {
+            match arg.as_str() {
+                "-c" => {
+                    self.dir_stack.clear();
+                    return 0;
+                }
+                "-v" => verbose = true,
+                "-p" => per_line = true,
+                _ => {
+                    self.error(&format!("dirs: {}: invalid option", arg), true);
+                    echoln_err("dirs: usage: dirs [-c] [-p] [-v]");
+                    return 2;
+                }
+            }
+        }
+
+        if !verbose && !per_line {
+            self.print_stack();
+            return 0;
+        }
+        for (i, entry) in self.shown_stack().iter().enumerate() {
+            if verbose {
+                echoln(&format!("{:>2}  {}", i, entry));
+            } else {
+                echoln(entry);
+            }
+        }
+        0
+    }
+}
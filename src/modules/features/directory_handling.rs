@@ -1,76 +1,194 @@
-use std::fs::{self, Metadata};
+use std::{
+    env,
+    fs::{self, Metadata},
+};
 
-use crate::shell::Shell;
+use crate::{path::normalize, shell::Shell, util::edit_distance};
 
-use super::echo::echoln;
+use super::echo::{echoln, echoln_err};
 
 impl Shell {
     pub fn pwd(&self) {
         echoln(self.abs_cwd.as_str());
     }
 
-    pub fn cd(&mut self, path: &str) -> Result<(), String> {
-        let path = path.trim();
+    pub fn handle_cd_command(&mut self, args: Vec<String>) -> i32 {
+        // -L (the default) keeps symlinks in the new path, -P resolves them
+        let mut physical = false;
+        let mut operands = args.as_slice();
+        while let Some(arg) = operands.first() {
+            if arg == "--" {
+                operands = &operands[1..];
+                break;
+            }
+            if arg.len() < 2 || !arg.starts_with('-') {
+                break;
+            }
+            for flag in arg[1..].chars() {
+                match flag {
+                    'L' => physical = false,
+                    'P' => physical = true,
+                    _ => {
+                        self.error(&format!("cd: -{}: invalid option", flag), true);
+                        echoln_err("cd: usage: cd [-L|-P] [dir]");
+                        return 2;
+                    }
+                }
+            }
+            operands = &operands[1..];
+        }
+
+        if operands.len() > 1 {
+            self.error("cd: too many arguments", true);
+            return 1;
+        }
+        let path = operands.first().map_or("", |path| path.trim());
+        match self.cd(path, physical) {
+            Ok(()) => 0,
+            Err(e) => {
+                self.error(&e, true);
+                1
+            }
+        }
+    }
 
+    pub fn cd(&mut self, path: &str, physical: bool) -> Result<(), String> {
         // if path empty, set to home dir
         if path.is_empty() {
-            self.current_dir = "~".to_string();
-            self.abs_cwd = self.home_dir.clone();
-            return Ok(());
+            let home = self.home_dir.clone();
+            return self.change_dir(&home, physical);
         }
 
-        // setting the path as a string
-        let mut abs_dir: String;
-
-        if path.starts_with('/') {
-            // meaning user entered absolute path
-            abs_dir = path.to_string();
-        } else if path.starts_with('~') {
-            // meaning user entered path starting with home dir
-            abs_dir = path.replace('~', &self.home_dir).to_string();
-        } else if path.starts_with("../") && !path[3..].starts_with(' ') {
-            //  entering directory from parent
-            let mut parts: Vec<&str> = self.abs_cwd.split('/').collect();
-            parts.pop();
-            abs_dir = parts.join("/") + "/" + &path[3..];
-        } else if path.starts_with("..") {
-            // entering parent
-            let mut parts: Vec<&str> = self.abs_cwd.split('/').collect();
-            parts.pop();
-            abs_dir = parts.join("/");
-        } else {
-            abs_dir = if self.abs_cwd == "/" {
-                format!("/{}", path)
-            } else {
-                format!("{}/{}", self.abs_cwd, path.strip_prefix("./").unwrap_or(path))
+        // `cd -` goes back to the previous directory and shows where it went
+        if path == "-" {
+            let Some(old_pwd) = self.get_var("OLDPWD").map(str::to_string) else {
+                return Err("cd: OLDPWD not set".to_string());
             };
+            self.change_dir(&old_pwd, physical)?;
+            self.pwd();
+            return Ok(());
         }
 
-        if abs_dir == "" {
-            // if abs dir is empty, the root has entered root
-            abs_dir = "/".to_string();
+        // a directory found through CDPATH or by fixing a typo is shown, since
+        // it is not where the path alone would lead
+        if let Some(found) = self.search_cdpath(path) {
+            self.change_dir(&found, physical)?;
+            self.pwd();
+            return Ok(());
         }
 
-        // checking path validity
-        path_exists_dir(&abs_dir)?;
+        let abs_dir = self.resolve_path(path).to_string_lossy().into_owned();
+        if self.options.cdspell
+            && path_exists_dir(&abs_dir).is_err()
+            && let Some(corrected) = correct_spelling(&normalize(&abs_dir))
+        {
+            self.change_dir(&corrected, physical)?;
+            self.pwd();
+            return Ok(());
+        }
+        self.change_dir(&abs_dir, physical)
+    }
+
+    /// Look for a relative directory under each entry of the colon-separated
+    /// CDPATH. Returns the directory only when it was found under a non-empty
+    /// entry; an empty entry stands for the current directory, which cd tries
+    /// anyway
+    fn search_cdpath(&self, path: &str) -> Option<String> {
+        // like POSIX shells, paths starting with /, . or .. are never searched,
+        // though names like `.config` are
+        let first = path.split('/').next().unwrap_or_default();
+        if path.starts_with('/') || path.starts_with('~') || first == "." || first == ".." {
+            return None;
+        }
+        let cdpath = self.get_var("CDPATH")?;
 
-        // if previous abs path is the home,
-        self.current_dir = if abs_dir.starts_with(&self.home_dir) {
-            abs_dir.replace(&self.home_dir, "~")
+        for entry in cdpath.split(':') {
+            if entry.is_empty() {
+                if path_exists_dir(&format!("{}/{}", self.abs_cwd, path)).is_ok() {
+                    return None;
+                }
+                continue;
+            }
+            let candidate = self.resolve_path(entry).join(path);
+            let candidate = candidate.to_string_lossy();
+            if path_exists_dir(&candidate).is_ok() {
+                return Some(candidate.into_owned());
+            }
+        }
+        None
+    }
+
+    /// Move the shell, and the process, to an absolute directory and record
+    /// the move in PWD, OLDPWD and the jump database. The new path has its `.` and `..`
+    /// components resolved as written, or, when `physical`, is the real path
+    /// with symlinks resolved
+    fn change_dir(&mut self, abs_dir: &str, physical: bool) -> Result<(), String> {
+        let abs_dir = if physical {
+            path_exists_dir(abs_dir)?;
+            let real = fs::canonicalize(abs_dir).map_err(|e| format!("cd: {}: {}", abs_dir, e))?;
+            real.to_string_lossy().into_owned()
         } else {
-            abs_dir.clone()
+            normalize(abs_dir)
         };
-        self.abs_cwd = abs_dir;
+
+        // checking path validity
+        path_exists_dir(&abs_dir)?;
+        env::set_current_dir(&abs_dir).map_err(|e| format!("cd: {}: {}", abs_dir, e))?;
+
+        self.current_dir = self.tilde_path(&abs_dir);
+        let old_pwd = std::mem::replace(&mut self.abs_cwd, abs_dir);
+        self.export_var("OLDPWD", &old_pwd);
+        self.export_var("PWD", &self.abs_cwd.clone());
+        self.record_visit(&self.abs_cwd);
 
         Ok(())
     }
+
+    /// Show a path under the home directory as `~/...`
+    pub fn tilde_path(&self, path: &str) -> String {
+        if self.home_dir == "/" {
+            return path.to_string();
+        }
+        match path.strip_prefix(&self.home_dir) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+            _ => path.to_string(),
+        }
+    }
 }
 
 pub fn path_exists_dir(path: &str) -> Result<Metadata, String> {
-    let metadata = fs::metadata(path).map_err(|_| format!("ru-shell: cd: {}: No such file or directory", path))?;
+    let metadata = fs::metadata(path).map_err(|_| format!("cd: {}: No such file or directory", path))?;
     if metadata.is_dir() {
         Ok(metadata)
     } else {
-        Err(format!("ru-shell: cd: {}: Not a directory", path))
+        Err(format!("cd: {}: Not a directory", path))
+    }
+}
+
+/// Find the existing directory closest to an absolute path that does not
+/// exist, replacing each missing component with the most similar directory
+/// name next to it. Only small differences count: one edit for short names,
+/// two for longer ones, with swapped neighbours counting as one edit
+fn correct_spelling(path: &str) -> Option<String> {
+    let mut corrected = String::new();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        let parent = if corrected.is_empty() { "/" } else { corrected.as_str() };
+        let exact = format!("{}/{}", corrected, component);
+        if path_exists_dir(&exact).is_ok() {
+            corrected = exact;
+            continue;
+        }
+
+        let max_distance = if component.chars().count() > 4 { 2 } else { 1 };
+        let best = fs::read_dir(parent)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .map(|name| (edit_distance(component, &name), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()?;
+        corrected = format!("{}/{}", corrected, best.1);
     }
+    Some(if corrected.is_empty() { "/".to_string() } else { corrected })
 }
@@ -1,36 +1,56 @@
-use std::ffi::CString;
+use std::io;
 
 use crate::shell::Shell;
 
-/*
-    In x86 asm, the syscall number for write is 1.
-    write syscall in nasm is 1
-    file descriptor for stdout is 1, and for stderr is 2, 0 for stdin.
-    then we need to pass a pointer to the string to rsi register and its length to rdx register.
-*/
-
 impl Shell {
-    pub fn handle_echo_command(args: Vec<String>) {
-        echoln(args.join(" ").as_str());
+    pub fn handle_echo_command(&self, args: Vec<String>) -> i32 {
+        let line = format!("{}\n", args.join(" "));
+        match write_all_fd(libc::STDOUT_FILENO, line.as_bytes()) {
+            Ok(()) => 0,
+            Err(e) => {
+                self.error(&format!("echo: write error: {}", e), true);
+                1
+            }
+        }
     }
 }
 
 pub fn echo(arg: &str) {
-    // removing the quotes from the string and
-    let c_string = CString::new(arg).expect("CString::new failed");
-
-    unsafe {
-        std::arch::asm!(
-            "syscall",
-            in("rax") 1, // syscall number for write
-            in("rdi") 1, // file descriptor for stdout
-            in("rsi") c_string.as_ptr(), // pointer to the string
-            in("rdx") c_string.as_bytes().len(), // length of the string
-        )
-    }
+    write_fd(1, arg);
 }
 
 pub fn echoln(arg: &str) {
     let arg = format!("{}\n", arg);
     echo(arg.as_str());
 }
+
+pub fn echo_err(arg: &str) {
+    write_fd(2, arg);
+}
+
+pub fn echoln_err(arg: &str) {
+    let arg = format!("{}\n", arg);
+    echo_err(arg.as_str());
+}
+
+/// Write all of `bytes` to a file descriptor, going on after short writes
+/// and interrupted calls
+pub fn write_all_fd(fd: i32, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        let written = unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+        if written < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        bytes = &bytes[written as usize..];
+    }
+    Ok(())
+}
+
+/// Messages from the shell itself have nowhere to report a failed write
+fn write_fd(fd: i32, arg: &str) {
+    let _ = write_all_fd(fd, arg.as_bytes());
+}
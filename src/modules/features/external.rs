@@ -0,0 +1,159 @@
+use std::{
+    ffi::CString,
+    fs,
+    io,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+
+use crate::shell::Shell;
+
+use super::jobs::Job;
+
+impl Shell {
+    pub fn run_external(&mut self, command: &str, args: &[String]) -> i32 {
+        let Some(program) = self.resolve_program(command) else {
+            self.error(&format!("{}: command not found", command), false);
+            return 127;
+        };
+
+        match self.spawn_and_wait(command, &program, args) {
+            Ok(status) => status,
+            Err(e) => {
+                self.error(&format!("{}: {}", command, e), true);
+                126
+            }
+        }
+    }
+
+    /// Find the executable a command name refers to; names containing a slash
+    /// are taken as paths instead of being searched on $PATH
+    pub fn resolve_program(&self, command: &str) -> Option<String> {
+        if !command.contains('/') {
+            return find_in_path(command, self.get_var("PATH").unwrap_or_default());
+        }
+
+        Some(self.resolve_path(command).to_string_lossy().into_owned())
+    }
+
+    /// Run the program as a foreground job of its own and wait for it
+    fn spawn_and_wait(&mut self, command: &str, program: &str, args: &[String]) -> io::Result<i32> {
+        // everything the child needs is allocated before forking
+        let exec_args = ExecArgs::new(command, program, args, &self.exported_env())?;
+        let c_cwd = to_cstring(&self.abs_cwd)?;
+
+        let pid = unsafe { libc::fork() };
+        if pid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if pid == 0 {
+            // child: run in the shell's working directory and replace the process image
+            self.enter_job(0, true);
+            if unsafe { libc::chdir(c_cwd.as_ptr()) } != 0 {
+                self.error(&format!("{}: {}: {}", command, self.abs_cwd, io::Error::last_os_error()), true);
+                unsafe { libc::_exit(1) };
+            }
+            self.exec_external(command, &exec_args);
+        }
+
+        let pgid = self.add_to_job(pid, 0);
+        let text = std::iter::once(command).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ");
+        let job = Job::new(self.job_pgid(pgid), vec![pid], text);
+        Ok(self.wait_foreground(job))
+    }
+
+    /// Replace the current (forked) process with the program, never returning
+    pub fn exec_external(&self, command: &str, exec_args: &ExecArgs) -> ! {
+        unsafe {
+            libc::execve(exec_args.program.as_ptr(), exec_args.argv.as_ptr(), exec_args.envp.as_ptr());
+        }
+        let err = io::Error::last_os_error();
+        self.error(&format!("{}: {}", command, err), true);
+        let code = if err.kind() == io::ErrorKind::NotFound { 127 } else { 126 };
+        unsafe { libc::_exit(code) }
+    }
+}
+
+/// The program path, argv and environment of an external command, converted
+/// ahead of a fork
+pub struct ExecArgs {
+    program: CString,
+    argv: Vec<*const libc::c_char>,
+    envp: Vec<*const libc::c_char>,
+    // own the strings `argv` and `envp` point into
+    _args: Vec<CString>,
+    _env: Vec<CString>,
+}
+
+impl ExecArgs {
+    pub fn new(command: &str, program: &str, args: &[String], env: &[String]) -> io::Result<Self> {
+        let mut c_args = vec![to_cstring(command)?];
+        for arg in args {
+            c_args.push(to_cstring(arg)?);
+        }
+        let c_env = env.iter().map(|var| to_cstring(var)).collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            program: to_cstring(program)?,
+            argv: null_terminated(&c_args),
+            envp: null_terminated(&c_env),
+            _args: c_args,
+            _env: c_env,
+        })
+    }
+}
+
+fn null_terminated(strings: &[CString]) -> Vec<*const libc::c_char> {
+    let mut pointers: Vec<*const libc::c_char> = strings.iter().map(|s| s.as_ptr()).collect();
+    pointers.push(std::ptr::null());
+    pointers
+}
+
+/// Wait for a child process to change state, retrying if interrupted by a
+/// signal, and return the raw wait status. `flags` are passed to waitpid
+pub fn wait_pid(pid: libc::pid_t, flags: i32) -> io::Result<i32> {
+    let mut status = 0;
+    loop {
+        let res = unsafe { libc::waitpid(pid, &mut status, flags) };
+        if res == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        return Ok(status);
+    }
+}
+
+/// Convert a raw wait status into a shell exit status, 128 + N for signal N
+pub fn exit_code(status: i32) -> i32 {
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    }
+}
+
+/// Search the directories listed in a $PATH value for an executable called `command`
+pub fn find_in_path(command: &str, path_var: &str) -> Option<String> {
+    path_var
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| format!("{}/{}", dir.trim_end_matches('/'), command))
+        .find(|candidate| is_executable(Path::new(candidate)))
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+pub fn to_cstring(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "argument contains a nul byte"))
+}
@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 impl Shell {
-    pub fn handle_copy_command(&mut self, args: Vec<String>) {
+    pub fn handle_copy_command(&mut self, args: Vec<String>) -> i32 {
         // Parse arguments for flags and paths
         let mut recursive = false;
         let mut paths = Vec::new();
@@ -21,31 +21,15 @@ impl Shell {
         if paths.len() < 2 {
             self.error("cp: missing file operand", true);
             echoln("Usage: cp [-r] SOURCE... DESTINATION");
-            return;
+            return 1;
         }
 
         // Last argument is the destination
         let dest_path = &paths[paths.len() - 1];
-        let destination_str = if dest_path.starts_with("/") {
-            dest_path.to_string()
-        } else if dest_path.starts_with("~") {
-            dest_path.replace("~", &self.home_dir)
-        } else {
-            format!("{}/{}", self.abs_cwd, dest_path)
-        };
-
-        let destination = Path::new(&destination_str);
-        let source_paths: Vec<String> = paths[0..paths.len() - 1]
+        let destination = self.resolve_path(dest_path);
+        let source_paths: Vec<PathBuf> = paths[0..paths.len() - 1]
             .iter()
-            .map(|source| {
-                if source.starts_with("/") {
-                    source.to_string()
-                } else if source.starts_with("~") {
-                    source.replace("~", &self.home_dir)
-                } else {
-                    format!("{}/{}", self.abs_cwd, source)
-                }
-            })
+            .map(|source| self.resolve_path(source))
             .collect();
 
         // If multiple sources, destination must be a directory
@@ -54,12 +38,13 @@ impl Shell {
                 &format!("cp: target '{}' is not a directory", destination.display()),
                 false,
             );
-            return;
+            return 1;
         }
 
         // Process each source
-        for source_str in &source_paths {
-            let source_path = Path::new(source_str);
+        let mut status = 0;
+        for source_path in &source_paths {
+            let source_str = source_path.display();
 
             if !source_path.exists() {
                 self.error(
@@ -69,6 +54,7 @@ impl Shell {
                     ),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
@@ -78,26 +64,30 @@ impl Shell {
                         &format!("cp: -r not specified; omitting directory '{}'", source_str),
                         false,
                     );
+                    status = 1;
                     continue;
                 }
 
                 // Get the destination path for this source
-                let dest_path = self.get_destination_path(source_path, destination);
+                let dest_path = self.get_destination_path(source_path, &destination);
 
                 // Copy directory recursively
                 if let Err(e) = self.copy_dir_recursive(source_path, &dest_path) {
                     self.error(&format!("cp: {}", e), false);
+                    status = 1;
                 }
             } else {
                 // Get the destination path for this source
-                let dest_path = self.get_destination_path(source_path, destination);
+                let dest_path = self.get_destination_path(source_path, &destination);
 
                 // Copy file
                 if let Err(e) = self.copy_file(source_path, &dest_path) {
                     self.error(&format!("cp: {}", e), false);
+                    status = 1;
                 }
             }
         }
+        status
     }
 
     fn get_destination_path(&self, source: &Path, destination: &Path) -> PathBuf {
@@ -159,38 +149,22 @@ impl Shell {
         Ok(())
     }
 
-    pub fn handle_move_command(&mut self, args: Vec<String>) {
+    pub fn handle_move_command(&mut self, args: Vec<String>) -> i32 {
         // Check if we have enough arguments
         if args.len() < 2 {
             self.error("mv: missing file operand", true);
             echoln("Usage: mv SOURCE... DESTINATION");
-            return;
+            return 1;
         }
 
         // Last argument is the destination
         let dest_path = &args[args.len() - 1];
-        let destination_str = if dest_path.starts_with("/") {
-            dest_path.to_string()
-        } else if dest_path.starts_with("~") {
-            dest_path.replace("~", &self.home_dir)
-        } else {
-            format!("{}/{}", self.abs_cwd, dest_path)
-        };
-
-        let destination = Path::new(&destination_str);
+        let destination = self.resolve_path(dest_path);
 
         // Process source paths
-        let source_paths: Vec<String> = args[0..args.len() - 1]
+        let source_paths: Vec<PathBuf> = args[0..args.len() - 1]
             .iter()
-            .map(|source| {
-                if source.starts_with("/") {
-                    source.to_string()
-                } else if source.starts_with("~") {
-                    source.replace("~", &self.home_dir)
-                } else {
-                    format!("{}/{}", self.abs_cwd, source)
-                }
-            })
+            .map(|source| self.resolve_path(source))
             .collect();
 
         // If multiple sources, destination must be a directory
@@ -199,12 +173,13 @@ impl Shell {
                 &format!("mv: target '{}' is not a directory", destination.display()),
                 false,
             );
-            return;
+            return 1;
         }
 
         // Process each source
-        for source_str in &source_paths {
-            let source_path = Path::new(source_str);
+        let mut status = 0;
+        for source_path in &source_paths {
+            let source_str = source_path.display();
 
             if !source_path.exists() {
                 self.error(
@@ -214,14 +189,15 @@ impl Shell {
                     ),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
             // Get the destination path for this source
-            let dest_path = self.get_destination_path(source_path, destination);
+            let dest_path = self.get_destination_path(source_path, &destination);
 
             // Check if source and destination are the same
-            if source_path == dest_path {
+            if *source_path == dest_path {
                 self.error(
                     &format!(
                         "mv: '{}' and '{}' are the same file",
@@ -230,6 +206,7 @@ impl Shell {
                     ),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
@@ -264,7 +241,9 @@ impl Shell {
             // Handle errors
             if let Err(e) = result {
                 self.error(&format!("mv: {}", e), false);
+                status = 1;
             }
         }
+        status
     }
 }
@@ -1,47 +1,42 @@
 use crate::shell::Shell;
 use std::fs;
-use std::path::Path;
 
 impl Shell {
-    pub fn handle_mkdir_command(&mut self, args: Vec<String>) {
+    pub fn handle_mkdir_command(&mut self, args: Vec<String>) -> i32 {
         if args.is_empty() {
             self.error("mkdir: missing operand", true);
-            return;
+            return 1;
         }
 
+        let mut status = 0;
         for dir in args {
-            let path_str = if dir.starts_with('/') {
-                dir.clone()
-            } else if dir.starts_with("~") {
-                dir.replace("~", &self.home_dir)
-            } else {
-                format!("{}/{}", self.abs_cwd, dir)
-            };
-
-            let path = Path::new(&path_str);
+            let path = self.resolve_path(&dir);
 
             if path.exists() {
                 self.error(
                     &format!("mkdir: cannot create directory '{}': File exists", dir),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
             // Try to create the directory
-            if let Err(e) = fs::create_dir(path) {
+            if let Err(e) = fs::create_dir(&path) {
                 self.error(
                     &format!("mkdir: cannot create directory '{}': {}", dir, e),
                     false,
                 );
+                status = 1;
             }
         }
+        status
     }
 
-    pub fn handle_rmdir_command(&mut self, args: Vec<String>) {
+    pub fn handle_rmdir_command(&mut self, args: Vec<String>) -> i32 {
         if args.is_empty() {
             self.error("rmdir: missing operand", true);
-            return;
+            return 1;
         }
 
         let mut recursive = false;
@@ -60,45 +55,41 @@ impl Shell {
 
         if filtered_args.is_empty() {
             self.error("rmdir: missing operand", true);
-            return;
+            return 1;
         }
 
+        let mut status = 0;
         for dir in filtered_args {
-            let path_str = if dir.starts_with('/') {
-                dir.clone()
-            } else if dir.starts_with("~") {
-                dir.replace("~", &self.home_dir)
-            } else {
-                format!("{}/{}", self.abs_cwd, dir)
-            };
-
-            let path = Path::new(&path_str);
+            let path = self.resolve_path(&dir);
 
             if !path.exists() {
                 self.error(
                     &format!("rmdir: cannot remove '{}': No such file or directory", dir),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
             // Try to remove the directory
             let result = if recursive {
-                fs::remove_dir_all(path)
+                fs::remove_dir_all(&path)
             } else {
-                fs::remove_dir(path)
+                fs::remove_dir(&path)
             };
 
             if let Err(e) = result {
                 self.error(&format!("rmdir: cannot remove '{}': {}", dir, e), false);
+                status = 1;
             }
         }
+        status
     }
 
-    pub fn handle_rm_command(&mut self, args: Vec<String>) {
+    pub fn handle_rm_command(&mut self, args: Vec<String>) -> i32 {
         if args.is_empty() {
             self.error("rm: missing operand", true);
-            return;
+            return 1;
         }
 
         // Parse for -r flag
@@ -115,19 +106,12 @@ impl Shell {
 
         if paths.is_empty() {
             self.error("rm: missing operand", true);
-            return;
+            return 1;
         }
 
+        let mut status = 0;
         for path_arg in paths {
-            let path_str = if path_arg.starts_with('/') {
-                path_arg.clone()
-            } else if path_arg.starts_with("~") {
-                path_arg.replace("~", &self.home_dir)
-            } else {
-                format!("{}/{}", self.abs_cwd, path_arg)
-            };
-
-            let path = Path::new(&path_str);
+            let path = self.resolve_path(&path_arg);
 
             if !path.exists() {
                 self.error(
@@ -137,6 +121,7 @@ impl Shell {
                     ),
                     false,
                 );
+                status = 1;
                 continue;
             }
 
@@ -147,19 +132,23 @@ impl Shell {
                         &format!("rm: cannot remove '{}': Is a directory", path_arg),
                         false,
                     );
+                    status = 1;
                     continue;
                 }
 
                 // Remove directory recursively
-                if let Err(e) = fs::remove_dir_all(path) {
+                if let Err(e) = fs::remove_dir_all(&path) {
                     self.error(&format!("rm: cannot remove '{}': {}", path_arg, e), false);
+                    status = 1;
                 }
             } else {
                 // Remove file
-                if let Err(e) = fs::remove_file(path) {
+                if let Err(e) = fs::remove_file(&path) {
                     self.error(&format!("rm: cannot remove '{}': {}", path_arg, e), false);
+                    status = 1;
                 }
             }
         }
+        status
     }
 }
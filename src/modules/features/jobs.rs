@@ -0,0 +1,501 @@
+use crate::{
+    external::{exit_code, wait_pid},
+    shell::Shell,
+};
+
+use super::echo::echoln;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done,
+}
+
+/// One process of a job
+#[derive(Debug, Clone)]
+struct Process {
+    pid: i32,
+    status: Option<i32>, // raw wait status, once the process has ended
+    stopped: bool,
+}
+
+/// A pipeline started by the shell, tracked until all of its processes end
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,       // 0 until the job is added to the job table
+    pub pgid: i32,       // 0 when the job shares the shell's process group
+    pub command: String, // the pipeline as written
+    processes: Vec<Process>,
+    changed: bool,       // whether the state changed since the user was last told
+}
+
+impl Job {
+    pub fn new(pgid: i32, pids: Vec<i32>, command: String) -> Self {
+        let processes = pids
+            .into_iter()
+            .map(|pid| Process { pid, status: None, stopped: false })
+            .collect();
+        Self { id: 0, pgid, command, processes, changed: false }
+    }
+
+    pub fn state(&self) -> JobState {
+        if self.processes.iter().all(|p| p.status.is_some()) {
+            JobState::Done
+        } else if self.processes.iter().any(|p| p.status.is_none() && p.stopped) {
+            JobState::Stopped
+        } else {
+            JobState::Running
+        }
+    }
+
+    /// Exit status of the job, which is the status of its last process
+    pub fn status(&self) -> i32 {
+        match self.processes.last().and_then(|p| p.status) {
+            Some(status) => exit_code(status),
+            None => 0,
+        }
+    }
+
+    /// The pid of the last process, as printed when the job is started
+    pub fn last_pid(&self) -> i32 {
+        self.processes.last().map_or(0, |p| p.pid)
+    }
+
+    fn live_pids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.processes.iter().filter(|p| p.status.is_none()).map(|p| p.pid)
+    }
+
+    /// Record a wait status reported for `pid`, returning false if the
+    /// process is not part of this job
+    fn update(&mut self, pid: i32, status: i32) -> bool {
+        let before = self.state();
+        let Some(process) = self.processes.iter_mut().find(|p| p.pid == pid) else {
+            return false;
+        };
+        if libc::WIFSTOPPED(status) {
+            process.stopped = true;
+        } else if libc::WIFCONTINUED(status) {
+            process.stopped = false;
+        } else {
+            process.status = Some(status);
+        }
+        if self.state() != before {
+            self.changed = true;
+        }
+        true
+    }
+
+    /// Send a signal to every process of the job
+    pub fn signal(&self, signal: i32) {
+        if self.pgid > 0 {
+            unsafe { libc::kill(-self.pgid, signal) };
+        } else {
+            for pid in self.live_pids() {
+                unsafe { libc::kill(pid, signal) };
+            }
+        }
+    }
+
+    /// Let a stopped job carry on
+    pub fn resume(&mut self) {
+        self.signal(libc::SIGCONT);
+        for process in &mut self.processes {
+            process.stopped = false;
+        }
+    }
+
+    /// The state as shown by `jobs`, with the exit status or signal of a
+    /// finished job
+    fn describe(&self) -> String {
+        match self.state() {
+            JobState::Running => "Running".to_string(),
+            JobState::Stopped => "Stopped".to_string(),
+            JobState::Done => {
+                let status = self.processes.last().and_then(|p| p.status).unwrap_or(0);
+                if libc::WIFSIGNALED(status) {
+                    signal_name(libc::WTERMSIG(status))
+                } else if libc::WEXITSTATUS(status) != 0 {
+                    format!("Exit {}", libc::WEXITSTATUS(status))
+                } else {
+                    "Done".to_string()
+                }
+            }
+        }
+    }
+}
+
+fn signal_name(signal: i32) -> String {
+    let name = unsafe { libc::strsignal(signal) };
+    if name.is_null() {
+        return format!("Signal {}", signal);
+    }
+    unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy().into_owned()
+}
+
+/// The jobs the shell is tracking, numbered from 1 like in other shells
+#[derive(Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    // job ids from least to most recently used; the last one is the current job
+    recent: Vec<usize>,
+}
+
+impl JobTable {
+    /// Add a job, giving it the next free number unless it already has one
+    pub fn add(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        }
+        let id = job.id;
+        self.jobs.push(job);
+        self.jobs.sort_by_key(|j| j.id);
+        self.recent.push(id);
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let index = self.jobs.iter().position(|j| j.id == id)?;
+        self.recent.retain(|recent| *recent != id);
+        Some(self.jobs.remove(index))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|j| j.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// `+` for the current job, `-` for the previous one
+    fn marker(&self, id: usize) -> char {
+        let mut recent = self.recent.iter().rev();
+        if recent.next() == Some(&id) {
+            '+'
+        } else if recent.next() == Some(&id) {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    fn format(&self, job: &Job) -> String {
+        let suffix = if job.state() == JobState::Running { " &" } else { "" };
+        format!("[{}]{}  {:<24}{}{}", job.id, self.marker(job.id), job.describe(), job.command, suffix)
+    }
+
+    /// Find the job a `%` job spec refers to: `%N` for job N, `%+` or `%%` for
+    /// the current job, `%-` for the previous one, `%name` for the job whose
+    /// command starts with name and `%?text` for the one containing text.
+    /// Without a spec, the current job
+    pub fn resolve(&self, spec: Option<&str>) -> Result<usize, String> {
+        let spec = spec.unwrap_or("%+");
+        let key = spec.strip_prefix('%').unwrap_or(spec);
+        let id = match key {
+            "" | "+" | "%" => self.recent.last().copied(),
+            "-" => self.recent.iter().rev().nth(1).copied(),
+            _ if key.chars().all(|c| c.is_ascii_digit()) => {
+                key.parse().ok().filter(|id| self.jobs.iter().any(|j| j.id == *id))
+            }
+            _ => {
+                let found: Vec<usize> = self
+                    .jobs
+                    .iter()
+                    .filter(|j| match key.strip_prefix('?') {
+                        Some(text) => j.command.contains(text),
+                        None => j.command.starts_with(key),
+                    })
+                    .map(|j| j.id)
+                    .collect();
+                if found.len() > 1 {
+                    return Err(format!("{}: ambiguous job spec", spec));
+                }
+                found.first().copied()
+            }
+        };
+        id.ok_or_else(|| format!("{}: no such job", spec))
+    }
+
+    /// Lines announcing the jobs whose state changed since the user was last
+    /// told, forgetting the finished ones
+    fn take_changes(&mut self) -> Vec<String> {
+        let lines = self.jobs.iter().filter(|j| j.changed).map(|j| self.format(j)).collect();
+        self.mark_reported();
+        lines
+    }
+
+    fn mark_reported(&mut self) {
+        for job in &mut self.jobs {
+            job.changed = false;
+        }
+        let done: Vec<usize> = self.jobs.iter().filter(|j| j.state() == JobState::Done).map(|j| j.id).collect();
+        for id in done {
+            self.remove(id);
+        }
+    }
+
+    /// Collect the status changes of background processes without blocking
+    fn reap(&mut self) {
+        loop {
+            let mut status = 0;
+            let flags = libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED;
+            let pid = unsafe { libc::waitpid(-1, &mut status, flags) };
+            if pid <= 0 {
+                break;
+            }
+            for job in &mut self.jobs {
+                if job.update(pid, status) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Shell {
+    /// Put the shell in its own process group in control of the terminal, so
+    /// jobs can be moved in and out of the foreground. Only done when stdin
+    /// is a terminal. The terminal is kept open on a descriptor of its own,
+    /// since stdin may be redirected while a job runs
+    pub fn init_job_control(&mut self) {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+            return;
+        }
+        // kept out of the way of redirections at 255 as in bash, or the first
+        // free descriptor from 10 on when the limit is lower, and closed in
+        // the programs the shell runs
+        let dup_from = |lowest: i32| unsafe { libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, lowest) };
+        let terminal = match dup_from(255) {
+            -1 => dup_from(10),
+            fd => fd,
+        };
+        if terminal < 0 {
+            return;
+        }
+        self.terminal = terminal;
+
+        unsafe {
+            // if started in the background, wait until we are brought to the foreground
+            loop {
+                let pgrp = libc::getpgrp();
+                if libc::tcgetpgrp(terminal) == pgrp {
+                    break;
+                }
+                libc::kill(-pgrp, libc::SIGTTIN);
+            }
+
+            // Ctrl-C and Ctrl-\ are meant for the foreground job, not the shell
+            libc::signal(libc::SIGINT, libc::SIG_IGN);
+            libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+            // the shell must not be stopped by its own terminal handling
+            libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+            libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+
+            // fails harmlessly when the shell already leads its session
+            libc::setpgid(0, 0);
+            libc::tcsetpgrp(terminal, libc::getpgrp());
+        }
+        self.interactive = true;
+    }
+
+    /// Set up a freshly forked child of a job: join the job's process group
+    /// (0 to start a new one), take the terminal if it runs in the
+    /// foreground, and restore the signals the shell ignores
+    pub fn enter_job(&self, pgid: i32, foreground: bool) {
+        unsafe {
+            if self.interactive {
+                libc::setpgid(0, pgid);
+                if foreground {
+                    libc::tcsetpgrp(self.terminal, libc::getpgrp());
+                }
+            }
+
+            // the Rust runtime ignores SIGPIPE, which would otherwise be inherited
+            for signal in [
+                libc::SIGPIPE,
+                libc::SIGINT,
+                libc::SIGQUIT,
+                libc::SIGTSTP,
+                libc::SIGTTIN,
+                libc::SIGTTOU,
+            ] {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+    }
+
+    /// Put a forked child into its job's process group from the parent side
+    /// too, so it is there whichever process runs first. Returns the job's
+    /// process group, which is the first child's pid
+    pub fn add_to_job(&self, pid: i32, pgid: i32) -> i32 {
+        let pgid = if pgid == 0 { pid } else { pgid };
+        if self.interactive {
+            unsafe { libc::setpgid(pid, pgid) };
+        }
+        pgid
+    }
+
+    /// Process group to record for a job, 0 when jobs share the shell's own
+    pub fn job_pgid(&self, pgid: i32) -> i32 {
+        if self.interactive { pgid } else { 0 }
+    }
+
+    /// Give the terminal to a job and wait until all of its processes end or
+    /// it is stopped, then take the terminal back. A stopped job (Ctrl-Z) goes
+    /// into the job table. Returns the job's exit status, or 128 + SIGTSTP if
+    /// it was stopped
+    pub fn wait_foreground(&mut self, mut job: Job) -> i32 {
+        // the job may leave the terminal in a different mode, e.g. an editor
+        let mut modes: libc::termios = unsafe { std::mem::zeroed() };
+        let saved_modes = self.interactive && unsafe { libc::tcgetattr(self.terminal, &mut modes) } == 0;
+        if self.interactive && job.pgid > 0 {
+            unsafe { libc::tcsetpgrp(self.terminal, job.pgid) };
+        }
+
+        while job.state() == JobState::Running {
+            let Some(pid) = job.processes.iter().find(|p| p.status.is_none() && !p.stopped).map(|p| p.pid) else {
+                break;
+            };
+            match wait_pid(pid, libc::WUNTRACED) {
+                Ok(status) => {
+                    job.update(pid, status);
+                }
+                Err(e) => {
+                    self.error(&format!("wait: {}", e), true);
+                    break;
+                }
+            }
+        }
+
+        if self.interactive {
+            unsafe {
+                libc::tcsetpgrp(self.terminal, libc::getpgrp());
+                if saved_modes {
+                    libc::tcsetattr(self.terminal, libc::TCSADRAIN, &modes);
+                }
+            }
+        }
+
+        if job.state() != JobState::Stopped {
+            // keep the next prompt off the line where ^C was echoed
+            if self.interactive && job.status() == 128 + libc::SIGINT {
+                echoln("");
+            }
+            return job.status();
+        }
+        // the user sees the stop right away, not at the next prompt
+        job.changed = false;
+        let id = self.jobs.add(job);
+        if let Some(job) = self.jobs.jobs.iter().find(|j| j.id == id) {
+            echoln("");
+            echoln(&self.jobs.format(job));
+        }
+        128 + libc::SIGTSTP
+    }
+
+    /// Start tracking a background job, printing its number and pid
+    pub fn add_background_job(&mut self, job: Job) {
+        let pid = job.last_pid();
+        let id = self.jobs.add(job);
+        if self.interactive {
+            echoln(&format!("[{}] {}", id, pid));
+        }
+    }
+
+    /// Tell the user about jobs that finished or stopped since the last
+    /// prompt, and forget the finished ones
+    pub fn notify_jobs(&mut self) {
+        self.jobs.reap();
+        for line in self.jobs.take_changes() {
+            echoln(&line);
+        }
+    }
+
+    pub fn handle_jobs_command(&mut self, args: Vec<String>) -> i32 {
+        let mut pids_only = false;
+        let mut long = false;
+        for arg in &args {
+            match arg.as_str() {
+                "-p" => pids_only = true,
+                "-l" => long = true,
+                _ => {
+                    self.error(&format!("jobs: {}: invalid option", arg), true);
+                    return 2;
+                }
+            }
+        }
+
+        self.jobs.reap();
+        for job in &self.jobs.jobs {
+            if pids_only {
+                echoln(&job.processes[0].pid.to_string());
+            } else if long {
+                let line = self.jobs.format(job);
+                let (number, rest) = line.split_at(line.find(' ').unwrap_or(0));
+                echoln(&format!("{} {}{}", number, job.processes[0].pid, rest));
+            } else {
+                echoln(&self.jobs.format(job));
+            }
+        }
+
+        self.jobs.mark_reported();
+        0
+    }
+
+    pub fn handle_fg_command(&mut self, args: Vec<String>) -> i32 {
+        let Some(mut job) = self.take_job("fg", &args) else {
+            return 1;
+        };
+        echoln(&job.command);
+        job.resume();
+        self.wait_foreground(job)
+    }
+
+    pub fn handle_bg_command(&mut self, args: Vec<String>) -> i32 {
+        let id = match self.jobs.resolve(args.first().map(String::as_str)) {
+            Ok(id) => id,
+            Err(e) => {
+                self.error(&format!("bg: {}", e), true);
+                return 1;
+            }
+        };
+        self.jobs.reap();
+
+        let Some(job) = self.jobs.get_mut(id) else {
+            self.error(&format!("bg: job {} has terminated", id), true);
+            return 1;
+        };
+        if job.state() == JobState::Running {
+            self.error(&format!("bg: job {} already in background", id), true);
+            return 0;
+        }
+        job.resume();
+        let command = job.command.clone();
+        self.jobs.recent.retain(|recent| *recent != id);
+        self.jobs.recent.push(id);
+        echoln(&format!("[{}]{} {} &", id, self.jobs.marker(id), command));
+        0
+    }
+
+    /// Remove the job named by a built-in's job spec argument from the table
+    fn take_job(&mut self, builtin: &str, args: &[String]) -> Option<Job> {
+        if args.len() > 1 {
+            self.error(&format!("{}: too many arguments", builtin), true);
+            return None;
+        }
+        let id = match self.jobs.resolve(args.first().map(String::as_str)) {
+            Ok(id) => id,
+            Err(e) => {
+                self.error(&format!("{}: {}", builtin, e), true);
+                return None;
+            }
+        };
+        self.jobs.remove(id)
+    }
+}
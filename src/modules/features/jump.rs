@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::shell::Shell;
+
+use super::echo::echoln;
+
+/// Once the ranks add up to more than this, they are all scaled down so old
+/// entries fade away
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+/// A directory in the jump database: how often it was visited, and when last
+#[derive(Debug, Clone, PartialEq)]
+struct Visit {
+    path: String,
+    rank: f64,
+    last_visit: u64, // seconds since the epoch
+}
+
+impl Visit {
+    /// Frequency weighted by recency, favouring the last hour, day and week
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_visit);
+        if age < 3600 {
+            self.rank * 4.0
+        } else if age < 86400 {
+            self.rank * 2.0
+        } else if age < 604800 {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Whether the fragments all appear in the path, each after the previous one
+fn matches_in_order(path: &str, fragments: &[String]) -> bool {
+    let mut rest = path;
+    for fragment in fragments {
+        match rest.find(fragment.as_str()) {
+            Some(i) => rest = &rest[i + fragment.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+impl Shell {
+    pub fn jump_db_path(&self) -> PathBuf {
+        PathBuf::from(&self.home_dir).join(".rushz")
+    }
+
+    /// Lines are `path|rank|last visit`, the same layout z uses
+    fn load_visits(&self) -> Vec<Visit> {
+        let Ok(content) = fs::read_to_string(self.jump_db_path()) else {
+            return Vec::new();
+        };
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.rsplitn(3, '|');
+                let last_visit = fields.next()?.parse().ok()?;
+                let rank = fields.next()?.parse().ok()?;
+                let path = fields.next()?.to_string();
+                Some(Visit { path, rank, last_visit })
+            })
+            .collect()
+    }
+
+    fn save_visits(&self, visits: &[Visit]) {
+        let content: String = visits
+            .iter()
+            .map(|v| format!("{}|{}|{}\n", v.path, v.rank, v.last_visit))
+            .collect();
+        let _ = fs::write(self.jump_db_path(), content);
+    }
+
+    /// Count a visit to a directory in the jump database
+    pub fn record_visit(&self, dir: &str) {
+        // the home directory is always one `cd` away
+        if dir == self.home_dir || dir.contains('\n') {
+            return;
+        }
+
+        let mut visits = self.load_visits();
+        match visits.iter_mut().find(|v| v.path == dir) {
+            Some(visit) => {
+                visit.rank += 1.0;
+                visit.last_visit = now();
+            }
+            None => visits.push(Visit { path: dir.to_string(), rank: 1.0, last_visit: now() }),
+        }
+
+        if visits.iter().map(|v| v.rank).sum::<f64>() > MAX_TOTAL_RANK {
+            for visit in &mut visits {
+                visit.rank *= 0.99;
+            }
+            visits.retain(|v| v.rank >= 1.0);
+        }
+        self.save_visits(&visits);
+    }
+
+    /// Existing directories matching the fragments, best match last. Matching
+    /// is case-sensitive unless that finds nothing
+    fn jump_candidates(&self, fragments: &[String]) -> Vec<(f64, String)> {
+        let now = now();
+        let visits: Vec<Visit> = self
+            .load_visits()
+            .into_iter()
+            .filter(|v| fs::metadata(&v.path).map(|m| m.is_dir()).unwrap_or(false))
+            .collect();
+
+        let mut candidates: Vec<(f64, String)> = visits
+            .iter()
+            .filter(|v| matches_in_order(&v.path, fragments))
+            .map(|v| (v.frecency(now), v.path.clone()))
+            .collect();
+        if candidates.is_empty() {
+            let lowered: Vec<String> = fragments.iter().map(|f| f.to_lowercase()).collect();
+            candidates = visits
+                .iter()
+                .filter(|v| matches_in_order(&v.path.to_lowercase(), &lowered))
+                .map(|v| (v.frecency(now), v.path.clone()))
+                .collect();
+        }
+
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        candidates
+    }
+
+    /// `z fragment...` jumps to the most frecent visited directory whose path
+    /// contains the fragments in order; `z -l` lists the candidates with their
+    /// scores instead
+    pub fn handle_jump_command(&mut self, name: &str, args: Vec<String>) -> i32 {
+        let list = args.first().is_some_and(|a| a == "-l");
+        let fragments = if list { &args[1..] } else { &args[..] };
+
+        let candidates = self.jump_candidates(fragments);
+        if list || fragments.is_empty() {
+            for (score, path) in &candidates {
+                echoln(&format!("{:<10.1} {}", score, path));
+            }
+            return 0;
+        }
+
+        let Some((_, best)) = candidates.last() else {
+            self.error(&format!("{}: no match for {}", name, fragments.join(" ")), true);
+            return 1;
+        };
+        let best = best.clone();
+        match self.cd(&best, false) {
+            Ok(()) => 0,
+            Err(e) => {
+                self.error(&e, true);
+                1
+            }
+        }
+    }
+}
@@ -2,7 +2,8 @@ use crate::shell::Shell;
 use libc;
 use std::{
     ffi::{CStr, CString, OsString},
-    os::unix::ffi::OsStringExt,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::Path,
     time::{Duration, UNIX_EPOCH},
 };
 
@@ -35,37 +36,38 @@ impl LsFlag {
     }
 }
 
+/// The options `from_char` and `from_long_option` accept, for completion
+pub const LS_OPTIONS: &[&str] = &["-l", "-a", "-F", "--long", "--all", "--classify"];
+
 impl Shell {
-    pub fn handle_ls_command(&self, args: Vec<String>) {
+    pub fn handle_ls_command(&self, args: Vec<String>) -> i32 {
         let (flags, paths) = parse_ls_args(args);
+        // when writing into a pipe or file, print one plain entry per line like ls does
+        let to_terminal = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+        let mut status = 0;
 
         for (i, path) in paths.iter().enumerate() {
             // print the path name as a header  if multiple paths
             if paths.len() > 1 {
                 echoln(&format!("{}/:", path));
             }
-            let path_to_list = if path == "." {
-                self.abs_cwd.clone()
-            } else if path.starts_with("/") || path.starts_with("~") {
-                path.to_string()
-            } else {
-                format!("{}/{}", self.abs_cwd, path)
-            };
+            let path_to_list = self.resolve_path(path);
 
-            let listings = match list_directory(&path_to_list, flags.clone()) {
+            let listings = match list_directory(&path_to_list, flags.clone(), to_terminal) {
                 Ok(entries) => entries,
                 Err(e) => {
-                    if e == path_to_list {
-                        vec![path_to_list.clone()]
+                    if e == path_to_list.display().to_string() {
+                        vec![e]
                     } else {
                         self.error(&format!("ls: {}", e), false);
+                        status = 1;
                         continue;
                     }
                 }
             };
 
             for entry in listings {
-                if flags.contains(&LsFlag::LongFormat) {
+                if flags.contains(&LsFlag::LongFormat) || !to_terminal {
                     // print the long format entry
                     echoln(&entry);
                 } else {
@@ -75,20 +77,23 @@ impl Shell {
 
             // print a new line only if not the last path
             if i != paths.len() - 1 {
-                echoln("\n");
+                echoln(if to_terminal { "\n" } else { "" });
             }
         }
-        echoln("");
+        if to_terminal {
+            echoln("");
+        }
+        status
     }
 }
 
-fn list_directory(dir_path: &str, flags: Vec<LsFlag>) -> Result<Vec<String>, String> {
+fn list_directory(dir_path: &Path, flags: Vec<LsFlag>, color: bool) -> Result<Vec<String>, String> {
     let all = flags.contains(&LsFlag::All);
     let long_format = flags.contains(&LsFlag::LongFormat);
     let classify = flags.contains(&LsFlag::Classify);
 
     // converting the path to a Clang string to use with LibC
-    let c_dir_path: CString = match CString::new(dir_path) {
+    let c_dir_path: CString = match CString::new(dir_path.as_os_str().as_bytes()) {
         Ok(path) => path,
         Err(e) => return Err(format!("ls: error converting to CString '{e}'")),
     };
@@ -98,11 +103,11 @@ fn list_directory(dir_path: &str, flags: Vec<LsFlag>) -> Result<Vec<String>, Str
         // SAFELY read errno
         let err = unsafe { *libc::__errno_location() };
         let err_msg = match err {
-            libc::ENOENT => format!("cannot access '{}': No such file or directory", dir_path),
-            libc::ENOTDIR => dir_path.to_string(),
+            libc::ENOENT => format!("cannot access '{}': No such file or directory", dir_path.display()),
+            libc::ENOTDIR => dir_path.display().to_string(),
             _ => format!(
                 "cannot open directory '{}': {}",
-                dir_path,
+                dir_path.display(),
                 std::io::Error::from_raw_os_error(err)
             ),
         };
@@ -121,86 +126,80 @@ fn list_directory(dir_path: &str, flags: Vec<LsFlag>) -> Result<Vec<String>, Str
             let d_name = (*entry).d_name.as_ptr();
             let c_name = CStr::from_ptr(d_name);
             let name = OsString::from_vec(c_name.to_bytes().to_vec());
-            if let Some(name_str) = name.to_str() {
-                if name_str.starts_with('.') && !all {
-                    continue;
-                }
+            // names that are not valid UTF-8 are shown lossily but still looked up exactly
+            let name_str = name.to_string_lossy();
+            if name_str.starts_with('.') && !all {
+                continue;
+            }
 
-                let full_path = format!("{}/{}", dir_path, name_str);
-                let c_full_path = CString::new(full_path.clone()).unwrap();
-                let mut stat_buf: libc::stat = std::mem::zeroed();
+            let full_path = dir_path.join(&name);
+            let c_full_path = CString::new(full_path.into_os_string().into_vec()).unwrap();
+            let mut stat_buf: libc::stat = std::mem::zeroed();
 
-                if libc::lstat(c_full_path.as_ptr(), &mut stat_buf) != 0 {
-                    continue; // skip on error
-                }
+            if libc::lstat(c_full_path.as_ptr(), &mut stat_buf) != 0 {
+                continue; // skip on error
+            }
 
-                let mut display_name = name_str.to_string();
-                if classify {
-                    let mode = stat_buf.st_mode;
-                    let ftype = mode & libc::S_IFMT;
-                    match ftype {
-                        libc::S_IFDIR => display_name.push('/'),
-                        libc::S_IFLNK => display_name.push('@'),
-                        libc::S_IFIFO => display_name.push('|'),
-                        libc::S_IFSOCK => display_name.push('='),
-                        libc::S_IFREG => {
-                            if (mode & 0o111) != 0 {
-                                display_name.push('*');
-                            }
-                        }
-                        _ => {}
-                    }
+            let mut display_name = name_str.to_string();
+            if classify {
+                let mode = stat_buf.st_mode;
+                let ftype = mode & libc::S_IFMT;
+                match ftype {
+                    libc::S_IFDIR => display_name.push('/'),
+                    libc::S_IFLNK => display_name.push('@'),
+                    libc::S_IFIFO => display_name.push('|'),
+                    libc::S_IFSOCK => display_name.push('='),
+                    libc::S_IFREG if (mode & 0o111) != 0 => display_name.push('*'),
+                    _ => {}
                 }
+            }
 
-                let f_type = get_file_type(stat_buf.st_mode);
-                if f_type == 'd' {
-                    display_name = format!("\x1b[1;34m{}\x1b[0m", display_name);
-                } else if f_type == 'l' {
-                    display_name = format!("\x1b[1;36m{}\x1b[0m", display_name);
-                }
-                if long_format {
-                    let perms = file_mode_string(stat_buf.st_mode);
-                    let nlink = stat_buf.st_nlink;
-                    // get user and group names from UIDs and GIDs
-                    let uid = stat_buf.st_uid;
-                    let gid = stat_buf.st_gid;
-
-                    // Look up and format username
-                    let pwd = libc::getpwuid(uid);
-                    let uid_name = if !pwd.is_null() {
-                        CStr::from_ptr((*pwd).pw_name).to_string_lossy().to_string()
-                    } else {
-                        uid.to_string() // Fallback to numeric ID if lookup fails
-                    };
+            let f_type = get_file_type(stat_buf.st_mode);
+            if color && f_type == 'd' {
+                display_name = format!("\x1b[1;34m{}\x1b[0m", display_name);
+            } else if color && f_type == 'l' {
+                display_name = format!("\x1b[1;36m{}\x1b[0m", display_name);
+            }
+            if long_format {
+                let perms = file_mode_string(stat_buf.st_mode);
+                let nlink = stat_buf.st_nlink;
+                // get user and group names from UIDs and GIDs
+                let uid = stat_buf.st_uid;
+                let gid = stat_buf.st_gid;
+
+                // Look up and format username
+                let pwd = libc::getpwuid(uid);
+                let uid_name = if !pwd.is_null() {
+                    CStr::from_ptr((*pwd).pw_name).to_string_lossy().to_string()
+                } else {
+                    uid.to_string() // Fallback to numeric ID if lookup fails
+                };
 
-                    // Look up and format group name
-                    let grp = libc::getgrgid(gid);
-                    let gid_name = if !grp.is_null() {
-                        CStr::from_ptr((*grp).gr_name).to_string_lossy().to_string()
-                    } else {
-                        gid.to_string() // Fallback to numeric ID if lookup fails
-                    };
-
-                    let size = stat_buf.st_size;
-                    let mtime = UNIX_EPOCH + Duration::from_secs(stat_buf.st_mtime as u64);
-                    let datetime = chrono::DateTime::<chrono::Local>::from(mtime);
-
-                    let formatted = format!(
-                        "{} {:>3} {} {} {:>8} {} {}",
-                        perms,
-                        nlink,
-                        uid_name,
-                        gid_name,
-                        size,
-                        datetime.format("%b %d %H:%M"),
-                        display_name
-                    );
-                    entry_names.push(formatted);
+                // Look up and format group name
+                let grp = libc::getgrgid(gid);
+                let gid_name = if !grp.is_null() {
+                    CStr::from_ptr((*grp).gr_name).to_string_lossy().to_string()
                 } else {
-                    entry_names.push(display_name);
-                }
+                    gid.to_string() // Fallback to numeric ID if lookup fails
+                };
+
+                let size = stat_buf.st_size;
+                let mtime = UNIX_EPOCH + Duration::from_secs(stat_buf.st_mtime as u64);
+                let datetime = chrono::DateTime::<chrono::Local>::from(mtime);
+
+                let formatted = format!(
+                    "{} {:>3} {} {} {:>8} {} {}",
+                    perms,
+                    nlink,
+                    uid_name,
+                    gid_name,
+                    size,
+                    datetime.format("%b %d %H:%M"),
+                    display_name
+                );
+                entry_names.push(formatted);
             } else {
-                return Err(format!("ls: error converting entry name to string"));
+                entry_names.push(display_name);
             }
         }
         libc::closedir(dir_ptr);
@@ -213,7 +212,7 @@ fn list_directory(dir_path: &str, flags: Vec<LsFlag>) -> Result<Vec<String>, Str
             let a_parts: Vec<&str> = a.split_whitespace().collect();
             let b_parts: Vec<&str> = b.split_whitespace().collect();
 
-            if a_parts.len() > 0 && b_parts.len() > 0 {
+            if !a_parts.is_empty() && !b_parts.is_empty() {
                 // Get the filename (last part) when in long format
                 let a_name = a_parts.last().unwrap();
                 let b_name = b_parts.last().unwrap();
@@ -245,24 +244,21 @@ fn parse_ls_args(args: Vec<String>) -> (Vec<LsFlag>, Vec<String>) {
     let mut paths = Vec::new();
 
     for arg in args {
-        if arg.starts_with("--") {
+        if let Some(option) = arg.strip_prefix("--") {
             // if not longer than 2, we mimick the behavoir of ls, ignoring it.
-            if arg.len() > 2 {
-                // handling long format options (--long, --all, etc.)
-                let option = &arg[2..]; // remove the leading "--"
-                if let Some(flag) = LsFlag::from_long_option(option) {
-                    if !flags.contains(&flag) {
-                        flags.push(flag);
-                    }
-                }
+            // handling long format options (--long, --all, etc.)
+            if let Some(flag) = LsFlag::from_long_option(option)
+                && !flags.contains(&flag)
+            {
+                flags.push(flag);
             }
         } else if arg.starts_with('-') {
             if arg.len() > 1 {
                 for c in arg.chars().skip(1) {
-                    if let Some(flag) = LsFlag::from_char(c) {
-                        if !flags.contains(&flag) {
-                            flags.push(flag);
-                        }
+                    if let Some(flag) = LsFlag::from_char(c)
+                        && !flags.contains(&flag)
+                    {
+                        flags.push(flag);
                     }
                 }
             }
@@ -4,5 +4,13 @@ pub mod ls;
 pub mod cat;
 pub mod file_ops;
 pub mod fs_ops;
+pub mod external;
+pub mod variables;
+pub mod shopt;
+pub mod jobs;
+pub mod dir_stack;
+pub mod jump;
+pub mod complete;
+pub mod source;
 
 pub use echo::echo;
@@ -0,0 +1,63 @@
+use crate::shell::Shell;
+
+use super::echo::echoln;
+
+/// Behaviour switches toggled with the shopt built-in
+#[derive(Debug, Clone, Default)]
+pub struct ShellOptions {
+    pub nullglob: bool, // patterns without matches expand to nothing
+    pub failglob: bool, // patterns without matches are an error
+    pub cdspell: bool,  // cd corrects small typos in directory names
+}
+
+impl ShellOptions {
+    const NAMES: &'static [&'static str] = &["cdspell", "failglob", "nullglob"];
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "nullglob" => Some(&mut self.nullglob),
+            "failglob" => Some(&mut self.failglob),
+            "cdspell" => Some(&mut self.cdspell),
+            _ => None,
+        }
+    }
+}
+
+impl Shell {
+    pub fn handle_shopt_command(&mut self, args: Vec<String>) -> i32 {
+        let (value, names) = match args.first().map(|a| a.as_str()) {
+            Some("-s") => (Some(true), &args[1..]),
+            Some("-u") => (Some(false), &args[1..]),
+            _ => (None, &args[..]),
+        };
+
+        if names.is_empty() {
+            // `-s` and `-u` alone list the options that are on or off
+            for name in ShellOptions::NAMES {
+                let state = self.options.get_mut(name).is_some_and(|option| *option);
+                if value.is_none_or(|value| value == state) {
+                    echoln(&format!("{:<16}{}", name, if state { "on" } else { "off" }));
+                }
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for name in names {
+            let Some(option) = self.options.get_mut(name) else {
+                self.error(&format!("shopt: {}: invalid shell option name", name), true);
+                status = 1;
+                continue;
+            };
+
+            match value {
+                Some(value) => *option = value,
+                None => {
+                    let state = if *option { "on" } else { "off" };
+                    echoln(&format!("{:<16}{}", name, state));
+                }
+            }
+        }
+        status
+    }
+}
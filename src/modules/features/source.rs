@@ -0,0 +1,56 @@
+use std::{fs, io, path::Path};
+
+use crate::{parse, shell::Shell};
+
+impl Shell {
+    /// Run the commands of a file, such as `~/.rushrc`, as if they were typed
+    /// in: one command per line, except that an unfinished line runs on into
+    /// the next. Errors name the file and line they come from. Returns the
+    /// status of the last command
+    pub fn source_file(&mut self, path: &Path) -> io::Result<i32> {
+        let content = fs::read_to_string(path)?;
+        let shown = self.tilde_path(&path.to_string_lossy());
+
+        let mut status = 0;
+        let mut command = String::new();
+        let mut first_line = 0;
+        for (i, line) in content.lines().enumerate() {
+            if command.is_empty() {
+                first_line = i + 1;
+            } else {
+                command.push('\n');
+            }
+            command.push_str(line);
+
+            match parse(&command) {
+                Err(e) if e.incomplete => continue,
+                Err(e) => {
+                    let line = first_line + command[..e.span.start.min(command.len())].matches('\n').count();
+                    self.source_location = Some((shown.clone(), line));
+                    self.error(&e.message, true);
+                    status = 2;
+                    self.last_status = status;
+                }
+                Ok(list) => {
+                    self.source_location = Some((shown.clone(), first_line));
+                    status = self.run_list(list);
+                }
+            }
+            self.source_location = None;
+            command.clear();
+            if self.should_exit {
+                return Ok(status);
+            }
+        }
+
+        // the file ended in the middle of a command
+        if let Err(e) = parse(&command) {
+            self.source_location = Some((shown, first_line));
+            self.error(&e.message, true);
+            self.source_location = None;
+            status = 2;
+            self.last_status = status;
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::{shell::Shell, util::is_valid_name};
+
+use super::echo::echoln;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub value: String,
+    pub exported: bool, // exported variables are passed to child processes
+}
+
+/// Load the process environment as the initial set of exported variables
+pub fn variables_from_env() -> HashMap<String, Variable> {
+    std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .map(|(name, value)| (name, Variable { value, exported: true }))
+        .collect()
+}
+
+impl Shell {
+    pub fn get_var(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|var| var.value.as_str())
+    }
+
+    /// Set a variable, keeping its exported flag if it already exists
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        match self.variables.get_mut(name) {
+            Some(var) => var.value = value.to_string(),
+            None => {
+                self.variables.insert(
+                    name.to_string(),
+                    Variable { value: value.to_string(), exported: false },
+                );
+            }
+        }
+    }
+
+    /// Set a variable and mark it for export
+    pub fn export_var(&mut self, name: &str, value: &str) {
+        self.variables.insert(
+            name.to_string(),
+            Variable { value: value.to_string(), exported: true },
+        );
+    }
+
+    /// The `NAME=value` strings handed to child processes as their environment
+    pub fn exported_env(&self) -> Vec<String> {
+        let mut env: Vec<String> = self
+            .variables
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, var)| format!("{}={}", name, var.value))
+            .collect();
+        env.sort();
+        env
+    }
+
+    /// Export variables for the duration of a single command, returning what
+    /// they replaced so `restore_variables` can put it back
+    pub fn set_temporary_vars(&mut self, assignments: Vec<(String, String)>) -> Vec<(String, Option<Variable>)> {
+        let mut saved = Vec::with_capacity(assignments.len());
+        for (name, value) in assignments {
+            let previous = self.variables.insert(name.clone(), Variable { value, exported: true });
+            saved.push((name, previous));
+        }
+        saved
+    }
+
+    pub fn restore_variables(&mut self, saved: Vec<(String, Option<Variable>)>) {
+        // restore in reverse so a name assigned twice ends up with its original value
+        for (name, previous) in saved.into_iter().rev() {
+            match previous {
+                Some(var) => self.variables.insert(name, var),
+                None => self.variables.remove(&name),
+            };
+        }
+    }
+
+    pub fn handle_export_command(&mut self, args: Vec<String>) -> i32 {
+        if args.is_empty() {
+            let mut names: Vec<&String> = self.variables.iter().filter(|(_, v)| v.exported).map(|(n, _)| n).collect();
+            names.sort();
+            for name in names {
+                echoln(&format!("export {}=\"{}\"", name, self.variables[name].value));
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+
+            if !is_valid_name(name) {
+                self.error(&format!("export: `{}': not a valid identifier", arg), true);
+                status = 1;
+                continue;
+            }
+
+            let var = self.variables.entry(name.to_string()).or_insert(Variable {
+                value: String::new(),
+                exported: true,
+            });
+            var.exported = true;
+            if let Some(value) = value {
+                var.value = value.to_string();
+            }
+        }
+        status
+    }
+
+    pub fn handle_unset_command(&mut self, args: Vec<String>) -> i32 {
+        let mut status = 0;
+        for name in args {
+            if !is_valid_name(&name) {
+                self.error(&format!("unset: `{}': not a valid identifier", name), true);
+                status = 1;
+                continue;
+            }
+            self.variables.remove(&name);
+        }
+        status
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{git_objects::ObjectStore, shell::Shell};
+
+/// How long a cached status is trusted when nothing under `.git` changed.
+/// Finding edits to the work tree means checking every tracked file, so
+/// they only show up after this long, or once the index is written
+const STATUS_TTL: Duration = Duration::from_secs(30);
+
+/// What the prompt shows about the repository the shell is in
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitStatus {
+    pub head: String,   // the branch name, or the abbreviated commit when detached
+    pub detached: bool,
+    pub dirty: bool,   // a tracked file differs from the index
+    pub ahead: usize,  // commits on the branch that its upstream does not have
+    pub behind: usize, // commits on the upstream that the branch does not have
+}
+
+impl GitStatus {
+    /// The prompt segment, like ` (main*)`, with `↑n` and `↓n` for the
+    /// commits the branch is ahead of and behind its upstream
+    pub fn segment(&self) -> String {
+        let mut segment = format!(" ({}", self.head);
+        if self.dirty {
+            segment.push('*');
+        }
+        if self.ahead > 0 || self.behind > 0 {
+            segment.push(' ');
+        }
+        if self.ahead > 0 {
+            segment.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            segment.push_str(&format!("↓{}", self.behind));
+        }
+        segment.push(')');
+        segment
+    }
+}
+
+/// A status worked out for a work tree, and what it was worked out from
+pub struct CachedStatus {
+    status: Option<GitStatus>,
+    stamps: Vec<Option<SystemTime>>,
+    checked: Instant,
+}
+
+/// Where a repository keeps its data
+struct Repo {
+    work_tree: PathBuf,
+    git_dir: PathBuf,
+    // shared refs and config, which differ from git_dir in linked worktrees
+    common_dir: PathBuf,
+}
+
+impl Repo {
+    /// The repository containing a directory, found by looking for `.git` in
+    /// it and each of its parents. `.git` may also be a file naming the real
+    /// git directory, as in worktrees and submodules
+    fn find(start: &Path) -> Option<Self> {
+        for dir in start.ancestors() {
+            let dot_git = dir.join(".git");
+            let git_dir = if dot_git.is_dir() {
+                dot_git
+            } else if let Ok(content) = fs::read_to_string(&dot_git) {
+                // a `.git` file that names no git directory is not a repository
+                let Some(target) = content.strip_prefix("gitdir:") else {
+                    continue;
+                };
+                dir.join(target.trim())
+            } else {
+                continue;
+            };
+
+            let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+                Ok(common) => git_dir.join(common.trim()),
+                Err(_) => git_dir.clone(),
+            };
+            return Some(Self { work_tree: dir.to_path_buf(), git_dir, common_dir });
+        }
+        None
+    }
+
+    /// The file a ref is kept in; HEAD and other per-worktree refs are in the
+    /// git directory, branches in the common one
+    fn ref_path(&self, name: &str) -> PathBuf {
+        let per_worktree = self.git_dir.join(name);
+        if per_worktree.exists() { per_worktree } else { self.common_dir.join(name) }
+    }
+
+    /// The commit a ref points at, following symbolic refs and falling back
+    /// to packed-refs
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut name = name.to_string();
+        // bound the chain of symbolic refs, as git does
+        for _ in 0..5 {
+            let content = match fs::read_to_string(self.ref_path(&name)) {
+                Ok(content) => content,
+                Err(_) => return self.packed_ref(&name),
+            };
+            match content.trim().strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_string(),
+                None => return Some(content.trim().to_string()),
+            }
+        }
+        None
+    }
+
+    fn packed_ref(&self, name: &str) -> Option<String> {
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .find_map(|line| match line.split_once(' ') {
+                Some((hash, ref_name)) if ref_name == name => Some(hash.to_string()),
+                _ => None,
+            })
+    }
+
+    /// The remote-tracking ref a branch follows, from its `[branch "name"]`
+    /// section in the config
+    fn upstream(&self, branch: &str) -> Option<String> {
+        let config = fs::read_to_string(self.common_dir.join("config")).ok()?;
+        let header = format!("[branch \"{}\"]", branch);
+        let (mut remote, mut merge) = (None, None);
+        let mut in_section = false;
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_section = line == header;
+            } else if in_section && let Some((key, value)) = line.split_once('=') {
+                match key.trim() {
+                    "remote" => remote = Some(value.trim().to_string()),
+                    "merge" => merge = Some(value.trim().to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        let merge = merge?;
+        match remote?.as_str() {
+            // a local branch as upstream
+            "." => Some(merge),
+            remote => Some(format!("refs/remotes/{}/{}", remote, merge.strip_prefix("refs/heads/")?)),
+        }
+    }
+
+    /// Modification times of the files the status is read from, to tell when
+    /// a cached status is out of date
+    fn stamps(&self) -> Vec<Option<SystemTime>> {
+        let mtime = |path: PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut stamps = vec![
+            mtime(self.git_dir.join("HEAD")),
+            mtime(self.git_dir.join("index")),
+            mtime(self.common_dir.join("packed-refs")),
+        ];
+        if let Some(branch) = self.branch() {
+            stamps.push(mtime(self.ref_path(&format!("refs/heads/{}", branch))));
+            if let Some(upstream) = self.upstream(&branch) {
+                stamps.push(mtime(self.ref_path(&upstream)));
+            }
+        }
+        stamps
+    }
+
+    /// The checked out branch, or None when HEAD is detached
+    fn branch(&self) -> Option<String> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let target = head.trim().strip_prefix("ref:")?.trim();
+        Some(target.strip_prefix("refs/heads/").unwrap_or(target).to_string())
+    }
+
+    /// How far the branch is ahead of and behind its upstream. Nothing is
+    /// shown for an unborn branch, one without an upstream, or one too far
+    /// from it to count quickly
+    fn ahead_behind(&self, branch: &str) -> (usize, usize) {
+        let local = self.resolve(&format!("refs/heads/{}", branch));
+        let remote = self.upstream(branch).and_then(|upstream| self.resolve(&upstream));
+        match (local, remote) {
+            (Some(local), Some(remote)) => {
+                ObjectStore::open(&self.common_dir.join("objects")).ahead_behind(&local, &remote).unwrap_or_default()
+            }
+            _ => (0, 0),
+        }
+    }
+
+    fn status(&self) -> Option<GitStatus> {
+        let (head, detached, (ahead, behind)) = match self.branch() {
+            Some(branch) => {
+                let counts = self.ahead_behind(&branch);
+                (branch, false, counts)
+            }
+            None => {
+                let commit = self.resolve("HEAD")?;
+                (commit.chars().take(7).collect(), true, (0, 0))
+            }
+        };
+        let dirty = index_dirty(&self.work_tree, &self.git_dir.join("index"));
+        Some(GitStatus { head, detached, dirty, ahead, behind })
+    }
+}
+
+fn be32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn be16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+/// Whether any file in the index looks changed in the work tree, going by its
+/// size and modification time the way git's quick check does. Unmerged
+/// entries count as changes. Index versions 2 to 4 are understood. The scan
+/// ends at the first change, so only a clean tree costs a stat per entry
+fn index_dirty(work_tree: &Path, index: &Path) -> bool {
+    let Ok(data) = fs::read(index) else {
+        // a repository without an index has nothing tracked yet
+        return false;
+    };
+    if data.len() < 12 || &data[..4] != b"DIRC" {
+        return false;
+    }
+    let version = be32(&data, 4);
+    let count = be32(&data, 8);
+
+    let mut pos = 12;
+    let mut path: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let entry_start = pos;
+        if pos + 62 > data.len() {
+            return false;
+        }
+        let mtime = be32(&data, pos + 8);
+        let mode = be32(&data, pos + 24);
+        let size = be32(&data, pos + 36);
+        let flags = be16(&data, pos + 60);
+        pos += 62;
+
+        // assume-valid and skip-worktree entries are not to be checked
+        let mut skip = flags & 0x8000 != 0;
+        if version >= 3 && flags & 0x4000 != 0 {
+            skip |= be16(&data, pos) & 0x4000 != 0;
+            pos += 2;
+        }
+
+        if version >= 4 {
+            // the path replaces the end of the previous one
+            let mut strip = 0usize;
+            loop {
+                let Some(&byte) = data.get(pos) else { return false };
+                pos += 1;
+                strip = (strip << 7) | (byte & 0x7f) as usize;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                strip += 1;
+            }
+            path.truncate(path.len().saturating_sub(strip));
+        } else {
+            path.clear();
+        }
+        let Some(nul) = data[pos..].iter().position(|b| *b == 0) else {
+            return false;
+        };
+        path.extend_from_slice(&data[pos..pos + nul]);
+        pos += nul + 1;
+        if version < 4 {
+            // entries are padded with NULs to a multiple of eight bytes
+            pos = entry_start + (pos - 1 - entry_start + 8) / 8 * 8;
+        }
+
+        let stage = (flags >> 12) & 0x3;
+        if stage != 0 {
+            return true;
+        }
+        // submodules are checked in their own repository
+        if skip || mode & 0o170000 == 0o160000 {
+            continue;
+        }
+        match fs::symlink_metadata(work_tree.join(OsStr::from_bytes(&path))) {
+            Ok(metadata) if metadata.size() as u32 == size && metadata.mtime() as u32 == mtime => {}
+            _ => return true,
+        }
+    }
+    false
+}
+
+impl Shell {
+    /// The git status for the current directory, worked out again only when
+    /// the repository's HEAD, index or refs change or the cached one is old.
+    /// Statuses are cached by work tree, so that moving around inside a
+    /// repository does not check it again
+    pub fn git_status(&mut self) -> Option<GitStatus> {
+        let repo = Repo::find(Path::new(&self.abs_cwd))?;
+        let stamps = repo.stamps();
+        if let Some(cached) = self.git_cache.get(&repo.work_tree)
+            && cached.stamps == stamps
+            && cached.checked.elapsed() < STATUS_TTL
+        {
+            return cached.status.clone();
+        }
+
+        let status = repo.status();
+        // forget repositories that have not been looked at in a while
+        self.git_cache.retain(|_, cached| cached.checked.elapsed() < STATUS_TTL);
+        let cached = CachedStatus { status: status.clone(), stamps, checked: Instant::now() };
+        self.git_cache.insert(repo.work_tree, cached);
+        status
+    }
+}
+
+/// Cached statuses by work tree
+pub type GitCache = HashMap<PathBuf, CachedStatus>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    const MAIN: &str = "1111111111111111111111111111111111111111";
+    const ORIGIN: &str = "2222222222222222222222222222222222222222";
+
+    /// An index entry: the path, and the flags and extended flags to give it
+    struct Entry(&'static str, u16, u16);
+
+    /// An index of the given version listing files of the fixture, with the
+    /// size and mtime they have on disk
+    fn write_index(fixture: &Fixture, version: u32, entries: &[Entry]) -> PathBuf {
+        let mut data = b"DIRC".to_vec();
+        data.extend(version.to_be_bytes());
+        data.extend((entries.len() as u32).to_be_bytes());
+        let mut previous = "";
+        for Entry(path, flags, extended) in entries {
+            let start = data.len();
+            let (mtime, size) = match fs::metadata(fixture.0.join(path)) {
+                Ok(metadata) => (metadata.mtime() as u32, metadata.size() as u32),
+                Err(_) => (0, 0),
+            };
+            data.extend([0; 8]);
+            data.extend(mtime.to_be_bytes());
+            data.extend([0; 12]);
+            data.extend(0o100644u32.to_be_bytes());
+            data.extend([0; 8]);
+            data.extend(size.to_be_bytes());
+            data.extend([0; 20]);
+            data.extend((flags | path.len() as u16).to_be_bytes());
+            if flags & 0x4000 != 0 {
+                data.extend(extended.to_be_bytes());
+            }
+            if version >= 4 {
+                // keep what is shared with the previous path, up to a short strip
+                let shared = path.bytes().zip(previous.bytes()).take_while(|(a, b)| a == b).count();
+                data.push((previous.len() - shared) as u8);
+                data.extend(path[shared..].bytes());
+                data.push(0);
+            } else {
+                data.extend(path.bytes());
+                data.extend(std::iter::repeat_n(0, 8 - (data.len() - start) % 8));
+            }
+            previous = path;
+        }
+        let index = fixture.0.join("index");
+        fs::write(&index, data).unwrap();
+        index
+    }
+
+    #[test]
+    fn index_versions_and_changes() {
+        let fixture = Fixture::new("git-index");
+        fixture.write("a.txt", "a");
+        fixture.write("dir/b.txt", "b");
+        fixture.write("dir/c.txt", "c");
+        let clean = [Entry("a.txt", 0, 0), Entry("dir/b.txt", 0, 0), Entry("dir/c.txt", 0, 0)];
+        for version in 2..=4 {
+            let index = write_index(&fixture, version, &clean);
+            assert!(!index_dirty(&fixture.0, &index), "version {}", version);
+        }
+
+        // the file is changed after the index was written
+        let index = write_index(&fixture, 4, &clean);
+        fixture.write("dir/c.txt", "changed");
+        assert!(index_dirty(&fixture.0, &index));
+
+        // a missing file, unless it is marked skip-worktree
+        let index = write_index(&fixture, 3, &[Entry("a.txt", 0, 0), Entry("gone.txt", 0x4000, 0)]);
+        assert!(index_dirty(&fixture.0, &index));
+        let index = write_index(&fixture, 3, &[Entry("a.txt", 0, 0), Entry("gone.txt", 0x4000, 0x4000)]);
+        assert!(!index_dirty(&fixture.0, &index));
+
+        // an unmerged entry
+        let index = write_index(&fixture, 2, &[Entry("a.txt", 0x2000, 0)]);
+        assert!(index_dirty(&fixture.0, &index));
+        assert!(!index_dirty(&fixture.0, &fixture.0.join("no-index")));
+    }
+
+    #[test]
+    fn refs_and_upstream() {
+        let fixture = Fixture::new("git-refs");
+        fixture.write(".git/HEAD", "ref: refs/heads/main\n");
+        fixture.write(".git/refs/heads/main", &format!("{}\n", MAIN));
+        fixture.write(
+            ".git/packed-refs",
+            &format!("# pack-refs with: peeled\n{} refs/remotes/origin/main\n^{}\n", ORIGIN, MAIN),
+        );
+        fixture.write(".git/config", "[core]\n\tbare = false\n[branch \"main\"]\n\tremote = origin\n\tmerge = refs/heads/main\n");
+        fixture.write("src/lib.rs", "");
+
+        let repo = Repo::find(&fixture.0.join("src")).unwrap();
+        assert_eq!(repo.work_tree, fixture.0);
+        assert_eq!(repo.branch().as_deref(), Some("main"));
+        assert_eq!(repo.resolve("HEAD").as_deref(), Some(MAIN));
+        assert_eq!(repo.resolve("refs/remotes/origin/main").as_deref(), Some(ORIGIN));
+        assert_eq!(repo.resolve("refs/heads/other"), None);
+        assert_eq!(repo.upstream("main").as_deref(), Some("refs/remotes/origin/main"));
+        assert_eq!(repo.upstream("other"), None);
+
+        // a detached HEAD, seen from a worktree whose .git is a file
+        fixture.write(".git/worktrees/wt/HEAD", &format!("{}\n", ORIGIN));
+        fixture.write(".git/worktrees/wt/commondir", "../..\n");
+        fixture.write("wt/.git", &format!("gitdir: {}\n", fixture.0.join(".git/worktrees/wt").display()));
+        let worktree = Repo::find(&fixture.0.join("wt")).unwrap();
+        assert_eq!(worktree.branch(), None);
+        assert_eq!(worktree.resolve("HEAD").as_deref(), Some(ORIGIN));
+        assert_eq!(worktree.resolve("refs/heads/main").as_deref(), Some(MAIN));
+
+        // a stray .git file does not hide the repository around it
+        fixture.write("src/.git", "not a git link\n");
+        assert_eq!(Repo::find(&fixture.0.join("src")).unwrap().work_tree, fixture.0);
+    }
+}
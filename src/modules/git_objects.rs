@@ -0,0 +1,416 @@
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::{self, File},
+    io::{BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use flate2::read::ZlibDecoder;
+
+/// How many commits the ahead/behind walk looks at before giving up, to keep
+/// the prompt quick when a branch and its upstream are far apart
+const WALK_LIMIT: usize = 10_000;
+
+/// How many bytes of delta bases a pack keeps around while a store is open
+const BASE_CACHE_LIMIT: usize = 32 << 20;
+
+const OBJ_COMMIT: u8 = 1;
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+type Hash = [u8; 20];
+
+/// An object's type and content
+type Object = (u8, Vec<u8>);
+
+fn parse_hash(hex: &str) -> Option<Hash> {
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut hash = [0; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+fn to_hex(hash: &Hash) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn inflate(reader: impl Read) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(reader).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// What the ahead/behind walk needs to know about a commit
+struct Commit {
+    parents: Vec<Hash>,
+    time: i64,
+}
+
+impl Commit {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let text = String::from_utf8_lossy(data);
+        let mut parents = Vec::new();
+        let mut time = 0;
+        // the headers end at the first blank line
+        for line in text.lines().take_while(|line| !line.is_empty()) {
+            if let Some(parent) = line.strip_prefix("parent ") {
+                parents.push(parse_hash(parent)?);
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                // `Name <email> 1700000000 +0100`
+                time = committer.rsplit(' ').nth(1)?.parse().ok()?;
+            }
+        }
+        Some(Self { parents, time })
+    }
+}
+
+/// A pack file and its version 2 index
+struct Pack {
+    index: File,
+    data: File,
+    count: usize,
+    fanout: Vec<u32>,
+    // objects already resolved as delta bases, by offset. Commits next to
+    // each other in history are often deltas against the same few bases,
+    // which would otherwise be inflated again for every one of them
+    bases: RefCell<HashMap<u64, Rc<Object>>>,
+    base_bytes: Cell<usize>,
+}
+
+impl Pack {
+    fn open(index_path: &Path) -> Option<Self> {
+        let index = File::open(index_path).ok()?;
+        let mut header = [0; 8 + 256 * 4];
+        index.read_exact_at(&mut header, 0).ok()?;
+        if header[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return None;
+        }
+        let fanout: Vec<u32> = header[8..].chunks(4).map(|n| u32::from_be_bytes([n[0], n[1], n[2], n[3]])).collect();
+        let data = File::open(index_path.with_extension("pack")).ok()?;
+        Some(Self {
+            index,
+            data,
+            count: fanout[255] as usize,
+            fanout,
+            bases: RefCell::default(),
+            base_bytes: Cell::new(0),
+        })
+    }
+
+    /// Where an object starts in the pack, found by a binary search of the
+    /// sorted names in the index
+    fn offset(&self, hash: &Hash) -> Option<u64> {
+        let names_start = 8 + 256 * 4;
+        let first = hash[0] as usize;
+        let mut low = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+        let mut high = self.fanout[first] as usize;
+        while low < high {
+            let middle = (low + high) / 2;
+            let mut name = [0; 20];
+            self.index.read_exact_at(&mut name, (names_start + middle * 20) as u64).ok()?;
+            match name.cmp(hash) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return self.offset_at(middle),
+            }
+        }
+        None
+    }
+
+    fn offset_at(&self, position: usize) -> Option<u64> {
+        let offsets_start = 8 + 256 * 4 + self.count * 24;
+        let mut offset = [0; 4];
+        self.index.read_exact_at(&mut offset, (offsets_start + position * 4) as u64).ok()?;
+        let offset = u32::from_be_bytes(offset);
+        if offset & 0x8000_0000 == 0 {
+            return Some(offset as u64);
+        }
+        // offsets past 2GiB are kept in a table of their own
+        let large_start = offsets_start + self.count * 4;
+        let mut large = [0; 8];
+        let position = (offset & 0x7fff_ffff) as usize;
+        self.index.read_exact_at(&mut large, (large_start + position * 8) as u64).ok()?;
+        Some(u64::from_be_bytes(large))
+    }
+
+    /// The object at an offset as the base of a delta, kept for the next
+    /// delta against it while there is room
+    fn base_at(&self, store: &ObjectStore, offset: u64) -> Option<Rc<Object>> {
+        if let Some(base) = self.bases.borrow().get(&offset) {
+            return Some(base.clone());
+        }
+        let base = Rc::new(self.read_at(store, offset)?);
+        let bytes = self.base_bytes.get() + base.1.len();
+        if bytes <= BASE_CACHE_LIMIT {
+            self.base_bytes.set(bytes);
+            self.bases.borrow_mut().insert(offset, base.clone());
+        }
+        Some(base)
+    }
+
+    /// The type and content of the object at an offset, with deltas applied
+    fn read_at(&self, store: &ObjectStore, offset: u64) -> Option<(u8, Vec<u8>)> {
+        let mut header = [0; 32];
+        let read = self.data.read_at(&mut header, offset).ok()?;
+        let header = &header[..read];
+
+        // the type, then the size in a varint that is not needed here
+        let kind = (header.first()? >> 4) & 0x7;
+        let mut pos = 1;
+        let mut byte = header[0];
+        while byte & 0x80 != 0 {
+            byte = *header.get(pos)?;
+            pos += 1;
+        }
+
+        let base = match kind {
+            OBJ_OFS_DELTA => {
+                let mut byte = *header.get(pos)?;
+                pos += 1;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = *header.get(pos)?;
+                    pos += 1;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                Some(self.base_at(store, offset.checked_sub(distance)?)?)
+            }
+            OBJ_REF_DELTA => {
+                let base: Hash = header.get(pos..pos + 20)?.try_into().ok()?;
+                pos += 20;
+                Some(store.read_base(&base)?)
+            }
+            _ => None,
+        };
+
+        let mut file = &self.data;
+        file.seek(SeekFrom::Start(offset + pos as u64)).ok()?;
+        let data = inflate(BufReader::new(file))?;
+        match base.as_deref() {
+            Some((base_kind, base_data)) => Some((*base_kind, apply_delta(base_data, &data)?)),
+            None => Some((kind, data)),
+        }
+    }
+}
+
+/// Build an object from its base and a delta made of copy and insert
+/// instructions
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    let mut varint = || {
+        let (mut value, mut shift) = (0usize, 0);
+        loop {
+            let byte = *delta.get(pos)?;
+            pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+    let base_size = varint()?;
+    let size = varint()?;
+    if base_size != base.len() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(size);
+    while let Some(&op) = delta.get(pos) {
+        pos += 1;
+        if op & 0x80 != 0 {
+            // which offset and size bytes follow is given by the low bits
+            let (mut offset, mut length) = (0usize, 0usize);
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= (*delta.get(pos)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    length |= (*delta.get(pos)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if length == 0 {
+                length = 0x10000;
+            }
+            out.extend_from_slice(base.get(offset..offset + length)?);
+        } else if op != 0 {
+            out.extend_from_slice(delta.get(pos..pos + op as usize)?);
+            pos += op as usize;
+        } else {
+            return None;
+        }
+    }
+    (out.len() == size).then_some(out)
+}
+
+/// A repository's object database, both loose objects and packs
+pub struct ObjectStore {
+    dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+impl ObjectStore {
+    pub fn open(dir: &Path) -> Self {
+        let packs = fs::read_dir(dir.join("pack"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+            .filter_map(|path| Pack::open(&path))
+            .collect();
+        Self { dir: dir.to_path_buf(), packs }
+    }
+
+    /// The type and content of an object
+    fn read(&self, hash: &Hash) -> Option<(u8, Vec<u8>)> {
+        let hex = to_hex(hash);
+        if let Ok(file) = File::open(self.dir.join(&hex[..2]).join(&hex[2..])) {
+            // `commit 123\0` and then the content
+            let data = inflate(BufReader::new(file))?;
+            let nul = data.iter().position(|b| *b == 0)?;
+            let kind = match data.split(|b| *b == b' ').next()? {
+                b"commit" => OBJ_COMMIT,
+                _ => 0,
+            };
+            return Some((kind, data[nul + 1..].to_vec()));
+        }
+        self.packs.iter().find_map(|pack| pack.read_at(self, pack.offset(hash)?))
+    }
+
+    /// An object that a delta in a pack is made against, cached like bases
+    /// found by offset when it is packed itself
+    fn read_base(&self, hash: &Hash) -> Option<Rc<Object>> {
+        match self.packs.iter().find_map(|pack| Some((pack, pack.offset(hash)?))) {
+            Some((pack, offset)) => pack.base_at(self, offset),
+            None => self.read(hash).map(Rc::new),
+        }
+    }
+
+    fn commit(&self, hash: &Hash) -> Option<Commit> {
+        match self.read(hash)? {
+            (OBJ_COMMIT, data) => Commit::parse(&data),
+            _ => None,
+        }
+    }
+
+    /// How many commits `local` has that `upstream` does not, and the other
+    /// way round, as `git rev-list --count --left-right local...upstream`
+    /// counts them. History is walked newest first from both ends until only
+    /// commits reachable from both are left. None when a commit cannot be
+    /// read or the walk gets too long
+    pub fn ahead_behind(&self, local: &str, upstream: &str) -> Option<(usize, usize)> {
+        const LOCAL: u8 = 1;
+        const UPSTREAM: u8 = 2;
+        const BOTH: u8 = LOCAL | UPSTREAM;
+
+        let (local, upstream) = (parse_hash(local)?, parse_hash(upstream)?);
+        if local == upstream {
+            return Some((0, 0));
+        }
+
+        let mut flags: HashMap<Hash, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (hash, flag) in [(local, LOCAL), (upstream, UPSTREAM)] {
+            let commit = self.commit(&hash)?;
+            flags.insert(hash, flag);
+            queue.push((commit.time, Reverse(hash), commit.parents));
+        }
+
+        // stop once every commit left to visit is reachable from both ends
+        // and has nothing more to pass on. Commits made in the same second
+        // can be visited out of order, so a commit found to be shared late
+        // still has to mark the ancestors seen before
+        let settled = |hash: &Hash, parents: &[Hash], flags: &HashMap<Hash, u8>| {
+            flags[hash] == BOTH && parents.iter().all(|parent| flags.get(parent).is_none_or(|flag| *flag == BOTH))
+        };
+        while queue.iter().any(|(_, Reverse(hash), parents)| !settled(hash, parents, &flags)) {
+            if flags.len() > WALK_LIMIT {
+                return None;
+            }
+            let (_, Reverse(hash), parents) = queue.pop()?;
+            let flag = flags[&hash];
+            for parent in parents {
+                let old = flags.get(&parent).copied().unwrap_or(0);
+                if old | flag == old {
+                    continue;
+                }
+                flags.insert(parent, old | flag);
+                let commit = self.commit(&parent)?;
+                queue.push((commit.time, Reverse(parent), commit.parents));
+            }
+        }
+
+        let count = |side| flags.values().filter(|flag| **flag == side).count();
+        Some((count(LOCAL), count(UPSTREAM)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Write;
+
+    /// Write a loose commit with the given parents and commit time, returning
+    /// its made-up name
+    fn commit(objects: &Path, name: u8, parents: &[u8], time: i64) -> String {
+        let mut content = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".to_string();
+        for parent in parents {
+            content.push_str(&format!("parent {}\n", to_hex(&[*parent; 20])));
+        }
+        content.push_str(&format!("author A <a@b> {} +0000\ncommitter A <a@b> {} +0000\n\nmessage\n", time, time));
+
+        let hex = to_hex(&[name; 20]);
+        let dir = objects.join(&hex[..2]);
+        fs::create_dir_all(&dir).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(format!("commit {}\0{}", content.len(), content).as_bytes()).unwrap();
+        fs::write(dir.join(&hex[2..]), encoder.finish().unwrap()).unwrap();
+        hex
+    }
+
+    #[test]
+    fn counts_commits_on_each_side() {
+        let objects = std::env::temp_dir().join(format!("ru-shell-objects-{}", std::process::id()));
+        // 1 - 2 - 3 - 4 - 5 (local, with a merge of 6 at 5)
+        //      \- 6 ----/
+        //          \- 7 - 8 (upstream)
+        commit(&objects, 1, &[], 100);
+        commit(&objects, 2, &[1], 200);
+        commit(&objects, 3, &[2], 300);
+        commit(&objects, 6, &[2], 300);
+        commit(&objects, 4, &[3], 400);
+        let local = commit(&objects, 5, &[4, 6], 500);
+        // made in the same second as commits on the other side
+        commit(&objects, 7, &[6], 400);
+        let upstream = commit(&objects, 8, &[7], 400);
+
+        let store = ObjectStore::open(&objects);
+        assert_eq!(store.ahead_behind(&local, &upstream), Some((3, 2)));
+        assert_eq!(store.ahead_behind(&upstream, &local), Some((2, 3)));
+        assert_eq!(store.ahead_behind(&local, &local), Some((0, 0)));
+        assert_eq!(store.ahead_behind(&local, &to_hex(&[9; 20])), None);
+        fs::remove_dir_all(&objects).unwrap();
+    }
+
+    #[test]
+    fn applies_deltas() {
+        // sizes 11 and 13, copy 6 bytes from 0, insert "there", copy 2 from 9
+        let delta = [11, 13, 0x91, 0, 6, 5, b't', b'h', b'e', b'r', b'e', 0x91, 9, 2];
+        assert_eq!(apply_delta(b"hello world", &delta).unwrap(), b"hello thereld".to_vec());
+        assert_eq!(apply_delta(b"hello", &delta), None);
+    }
+}
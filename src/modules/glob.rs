@@ -0,0 +1,294 @@
+use std::fs;
+
+/// Whether a pattern contains an unescaped `*`, `?` or `[`
+pub fn has_glob_chars(pattern: &str) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '*' || c == '?' || c == '[' {
+            return true;
+        }
+    }
+    false
+}
+
+/// Expand a glob pattern into the sorted list of paths it matches. Relative
+/// patterns are matched against `cwd` and produce relative paths. A `**`
+/// component matches any number of directories, and hidden names are only
+/// matched by a pattern that starts with a literal dot
+pub fn glob(pattern: &str, cwd: &str) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+
+    // each candidate is the path as it will be shown, plus the path on disk
+    let start = if absolute {
+        ("/".to_string(), "/".to_string())
+    } else {
+        (String::new(), cwd.to_string())
+    };
+    let mut candidates = vec![start];
+
+    for (i, component) in components.iter().enumerate() {
+        let is_last = i == components.len() - 1;
+        let mut next = Vec::new();
+
+        for (shown, on_disk) in &candidates {
+            if *component == "**" {
+                // zero or more directories (or, at the end, everything below)
+                if !is_last {
+                    next.push((shown.clone(), on_disk.clone()));
+                }
+                walk_recursive(shown, on_disk, is_last, &mut next);
+            } else if !has_glob_chars(component) {
+                let name = unescape(component);
+                next.push((join(shown, &name), join(on_disk, &name)));
+            } else {
+                for name in read_dir_names(on_disk) {
+                    if is_hidden_match_allowed(component, &name) && matches(component, &name) {
+                        next.push((join(shown, &name), join(on_disk, &name)));
+                    }
+                }
+            }
+        }
+
+        // intermediate components must be directories
+        if !is_last {
+            next.retain(|(_, on_disk)| fs::metadata(on_disk).map(|m| m.is_dir()).unwrap_or(false));
+        }
+        candidates = next;
+    }
+
+    let mut matches: Vec<String> = candidates
+        .into_iter()
+        .filter(|(shown, on_disk)| !shown.is_empty() && fs::symlink_metadata(on_disk).is_ok())
+        .filter(|(_, on_disk)| !pattern.ends_with('/') || fs::metadata(on_disk).map(|m| m.is_dir()).unwrap_or(false))
+        .map(|(shown, _)| {
+            if pattern.ends_with('/') && !shown.ends_with('/') {
+                shown + "/"
+            } else {
+                shown
+            }
+        })
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+fn walk_recursive(shown: &str, on_disk: &str, include_files: bool, out: &mut Vec<(String, String)>) {
+    for name in read_dir_names(on_disk) {
+        if name.starts_with('.') {
+            continue;
+        }
+        let child_shown = join(shown, &name);
+        let child_on_disk = join(on_disk, &name);
+        // do not follow symlinked directories to avoid cycles
+        let is_dir = fs::symlink_metadata(&child_on_disk)
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+
+        if is_dir || include_files {
+            out.push((child_shown.clone(), child_on_disk.clone()));
+        }
+        if is_dir {
+            walk_recursive(&child_shown, &child_on_disk, include_files, out);
+        }
+    }
+}
+
+fn read_dir_names(dir: &str) -> Vec<String> {
+    match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn is_hidden_match_allowed(pattern: &str, name: &str) -> bool {
+    !name.starts_with('.') || pattern.starts_with('.') || pattern.starts_with("\\.")
+}
+
+fn join(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{}{}", base, name)
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+/// Remove the backslashes that protect glob characters
+pub fn unescape(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped || c != '\\' {
+            result.push(c);
+            escaped = false;
+        } else {
+            escaped = true;
+        }
+    }
+    result
+}
+
+/// Match a single file name against a pattern component
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // where to resume after the most recent `*`
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match match_class(&pattern[p..], name[n]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                // an unterminated class is an ordinary `[`
+                None if name[n] == '[' => Some(1),
+                None => None,
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == name[n]).then_some(2),
+            Some(c) => (*c == name[n]).then_some(1),
+            None => None,
+        };
+
+        match step {
+            Some(len) => {
+                p += len;
+                n += 1;
+            }
+            None => match backtrack {
+                // let the last `*` swallow one more character
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a `[...]` class at the start of `pattern`, returning whether `c` is
+/// in it and the length of the class, or None if the class is not closed
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut found = false;
+    let mut first = true;
+    loop {
+        let current = *pattern.get(i)?;
+        // a `]` right after the opening bracket is a member, not the end
+        if current == ']' && !first {
+            return Some((found != negated, i + 1));
+        }
+        first = false;
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|end| *end != ']') {
+            let end = pattern[i + 2];
+            if current <= c && c <= end {
+                found = true;
+            }
+            i += 3;
+        } else {
+            if current == c {
+                found = true;
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    /// A scratch directory holding the given empty files
+    fn fixture(name: &str, files: &[&str]) -> Fixture {
+        let fixture = Fixture::new(&format!("glob-{}", name));
+        for file in files {
+            fixture.write(file, "");
+        }
+        fixture
+    }
+
+    fn glob_in(fixture: &Fixture, pattern: &str) -> Vec<String> {
+        glob(pattern, fixture.0.to_str().unwrap())
+    }
+
+    #[test]
+    fn star_backtracks() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("a*b*c", "axxbyybc"));
+        assert!(matches("*a*", "banana"));
+        assert!(!matches("a*b", "axxbx"));
+        assert!(matches("**", ""));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[abc]x", "bx"));
+        assert!(matches("file[0-9]", "file7"));
+        assert!(!matches("[!a-z]", "q"));
+        assert!(matches("[^a-z]", "Q"));
+        // a `]` right after the opening bracket is a member
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[!]]", "x"));
+        assert!(!matches("[!]]", "]"));
+        // a `-` at the end is literal, and an unclosed `[` is ordinary
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[ab", "[ab"));
+    }
+
+    #[test]
+    fn escaped_characters_are_literal() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(matches("a\\?", "a?"));
+        assert!(has_glob_chars("a*") && !has_glob_chars("a\\*"));
+        assert_eq!(unescape("a\\*b\\\\"), "a*b\\");
+    }
+
+    #[test]
+    fn hidden_files_need_a_leading_dot() {
+        let fixture = fixture("hidden", &[".env", "a.txt", "b.txt"]);
+        assert_eq!(glob_in(&fixture, "*"), vec!["a.txt", "b.txt"]);
+        assert_eq!(glob_in(&fixture, ".*"), vec![".env"]);
+        assert_eq!(glob_in(&fixture, "?.txt"), vec!["a.txt", "b.txt"]);
+        assert!(glob_in(&fixture, "*.md").is_empty());
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        let fixture = fixture("recursive", &["src/main.rs", "src/a/b/deep.rs", "src/.hidden/x.rs", "README"]);
+        assert_eq!(glob_in(&fixture, "src/**/*.rs"), vec!["src/a/b/deep.rs", "src/main.rs"]);
+        assert_eq!(glob_in(&fixture, "**/"), vec!["src/", "src/a/", "src/a/b/"]);
+        assert_eq!(glob_in(&fixture, "*/*.rs"), vec!["src/main.rs"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{borrow::Cow, cell::{Cell, RefCell}, rc::Rc};
+
+use rustyline::{
+    Cmd, ConditionalEventHandler, Context, Event, EventContext, Helper, RepeatCount,
+    completion::{Completer, Pair},
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    validate::Validator,
+};
+
+use crate::{
+    prompt::{RightPrompt, display_width, terminal_columns},
+    shell::Shell,
+};
+
+/// The line editor's view of the shell. It shares the shell with the REPL
+/// loop, which never holds on to it while a line is being read
+pub struct ShellHelper {
+    shell: Rc<RefCell<Shell>>,
+    right_prompt: Option<RightPrompt>,
+    hint_width: Cell<usize>, // the width of the suggestion shown after the line
+}
+
+impl ShellHelper {
+    pub fn new(shell: Rc<RefCell<Shell>>) -> Self {
+        Self { shell, right_prompt: None, hint_width: Cell::new(0) }
+    }
+
+    /// The right prompt to draw beside the line being read, if any
+    pub fn set_right_prompt(&mut self, right_prompt: Option<RightPrompt>) {
+        self.right_prompt = right_prompt;
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.shell.borrow().complete(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+
+    /// Suggest the rest of a previous command, only while typing at the end
+    /// of the line
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        let hint = if line.is_empty() || pos < line.len() { None } else { self.shell.borrow().history_hint(line) };
+        self.hint_width.set(hint.as_deref().map_or(0, display_width));
+        hint
+    }
+}
+
+impl Highlighter for ShellHelper {
+    /// The coloured line, followed by the right prompt while there is room
+    /// for it. Drawing it here keeps it out of the prompt width the editor
+    /// works with, and has it cleared and redrawn along with the line
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut highlighted = self.shell.borrow().highlight(line);
+        if let Some(right_prompt) = &self.right_prompt
+            && !line.contains('\n')
+            && let Some(codes) = right_prompt.draw(display_width(line) + self.hint_width.get(), terminal_columns())
+        {
+            highlighted.push_str(&codes);
+        }
+        Cow::Owned(highlighted)
+    }
+
+    /// Any edit can change how the whole line is coloured, moving the cursor
+    /// cannot
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        kind != CmdKind::MoveCursor
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[90m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// End accepts the whole suggestion, as Right already does; elsewhere it
+/// keeps moving to the end of the line
+pub struct AcceptHint;
+
+impl ConditionalEventHandler for AcceptHint {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        (ctx.has_hint() && ctx.pos() == ctx.line().len()).then_some(Cmd::CompleteHint)
+    }
+}
+
+/// Alt-F takes the next word of the suggestion, with the spaces before it;
+/// without a suggestion it moves forward a word as usual
+pub struct AcceptHintWord;
+
+impl ConditionalEventHandler for AcceptHintWord {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if ctx.pos() != ctx.line().len() {
+            return None;
+        }
+        let hint = ctx.hint_text()?;
+        let word_start = hint.len() - hint.trim_start().len();
+        let word_end = hint[word_start..].find(char::is_whitespace).map_or(hint.len(), |end| word_start + end);
+        Some(Cmd::Insert(1, hint[..word_end].to_string()))
+    }
+}
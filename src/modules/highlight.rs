@@ -0,0 +1,147 @@
+use std::{fs, path::Path};
+
+use crate::{exec::is_builtin, external::is_executable, shell::Shell, util::is_valid_name};
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const MAGENTA: &str = "\x1b[35m";
+const DEFAULT_COLOR: &str = "\x1b[39m";
+const UNDERLINE: &str = "\x1b[4m";
+const NO_UNDERLINE: &str = "\x1b[24m";
+
+/// Operators in the order they have to be tried, longest first
+const OPERATORS: &[&str] = &["&>>", "&>", "&&", "||", ">>", ">&", "<&", "|", "&", ";", "<", ">"];
+
+/// Length of the operator at the start of `rest`, counting the descriptor
+/// digits of a redirection like `2>`
+fn operator_len(rest: &str) -> Option<usize> {
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let after_digits = &rest[digits..];
+    OPERATORS.iter().find(|op| after_digits.starts_with(*op)).and_then(|op| {
+        let is_redirect = op.contains(['<', '>']);
+        // digits only belong to redirections
+        if digits > 0 && !is_redirect { None } else { Some(digits + op.len()) }
+    })
+}
+
+/// A word of the line: where it ends, its text with quotes and escapes
+/// removed, and the text with its quoted parts coloured
+struct ScannedWord {
+    end: usize,
+    value: String,
+    rendered: String,
+}
+
+fn scan_word(line: &str, start: usize) -> ScannedWord {
+    let mut value = String::new();
+    let mut rendered = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut end = line.len();
+
+    for (i, c) in line[start..].char_indices() {
+        let i = start + i;
+        if escaped {
+            value.push(c);
+            rendered.push(c);
+            escaped = false;
+            continue;
+        }
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+                rendered.push(c);
+                rendered.push_str(DEFAULT_COLOR);
+                continue;
+            }
+            Some('"') if c == '\\' => escaped = true,
+            Some(_) => value.push(c),
+            None => match c {
+                '\\' => escaped = true,
+                '\'' | '"' => {
+                    quote = Some(c);
+                    rendered.push_str(YELLOW);
+                }
+                c if c.is_whitespace() || "|;&<>".contains(c) => {
+                    end = i;
+                    break;
+                }
+                _ => value.push(c),
+            },
+        }
+        rendered.push(c);
+    }
+    // an unterminated quote colours the rest of the line
+    if quote.is_some() {
+        rendered.push_str(DEFAULT_COLOR);
+    }
+    ScannedWord { end, value, rendered }
+}
+
+impl Shell {
+    /// Colour a command line as it is typed: command names green when they
+    /// are a built-in or an executable and red otherwise, quoted text yellow,
+    /// operators and redirections in their own colours, and arguments that
+    /// name an existing file underlined. The text itself is left unchanged
+    pub fn highlight(&self, line: &str) -> String {
+        let mut out = String::with_capacity(line.len() * 2);
+        // whether the next word names the command, or the file of a redirection
+        let mut command_next = true;
+        let mut redirect_target = false;
+        let mut pos = 0;
+
+        while pos < line.len() {
+            let rest = &line[pos..];
+            let c = rest.chars().next().unwrap_or_default();
+            if c.is_whitespace() {
+                out.push(c);
+                pos += c.len_utf8();
+                continue;
+            }
+
+            if let Some(len) = operator_len(rest) {
+                let op = &rest[..len];
+                if op.contains(['<', '>']) {
+                    redirect_target = true;
+                    out.push_str(&format!("{}{}{}", MAGENTA, op, DEFAULT_COLOR));
+                } else {
+                    command_next = true;
+                    redirect_target = false;
+                    out.push_str(&format!("{}{}{}", CYAN, op, DEFAULT_COLOR));
+                }
+                pos += len;
+                continue;
+            }
+
+            let word = scan_word(line, pos);
+            let raw = &line[pos..word.end];
+            if redirect_target {
+                redirect_target = false;
+                out.push_str(&self.underline_if_file(&word));
+            } else if command_next && raw.split_once('=').is_some_and(|(name, _)| is_valid_name(name)) {
+                // assignments before the command are not the command
+                out.push_str(&word.rendered);
+            } else if command_next {
+                command_next = false;
+                // a path is only resolved, not checked, so see that it runs
+                let known = is_builtin(&word.value)
+                    || self.resolve_program(&word.value).is_some_and(|program| is_executable(Path::new(&program)));
+                out.push_str(&format!("{}{}{}", if known { GREEN } else { RED }, raw, DEFAULT_COLOR));
+            } else {
+                out.push_str(&self.underline_if_file(&word));
+            }
+            pos = word.end;
+        }
+        out
+    }
+
+    fn underline_if_file(&self, word: &ScannedWord) -> String {
+        if !word.value.is_empty() && fs::symlink_metadata(self.resolve_path(&word.value)).is_ok() {
+            format!("{}{}{}", UNDERLINE, word.rendered, NO_UNDERLINE)
+        } else {
+            word.rendered.clone()
+        }
+    }
+}
@@ -0,0 +1,632 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use crate::{
+    ast::{RedirectKind, Word, WordPart},
+    util::is_valid_name,
+};
+
+/// A byte range of the input line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(Word),
+    Assignment(String, Word), // NAME=value
+    Pipe,
+    And,        // &&
+    Or,         // ||
+    Semi,       // ;
+    Newline,    // an unquoted newline, which ends a command like `;`
+    Background, // &
+    Redirect(Option<i32>, RedirectKind),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// A lexing or parsing error, pointing at the part of the line it is about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    pub incomplete: bool, // the line ends too early, and more input could complete it
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self { message: message.into(), span, incomplete: false }
+    }
+
+    /// An error for a line that stops in the middle of something
+    pub fn unfinished(message: impl Into<String>, span: Span) -> Self {
+        Self { message: message.into(), span, incomplete: true }
+    }
+
+    /// The message followed by the offending line, with carets under the span
+    pub fn render(&self, input: &str) -> String {
+        let start = self.span.start.min(input.len());
+        let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+        let end = self.span.end.clamp(start, line_end);
+
+        // keep tabs so the caret lines up with the line above
+        let padding: String = input[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = input[start..end].chars().count().max(1);
+
+        format!(
+            "{}\n{}\n{}{}",
+            self.message,
+            &input[line_start..line_end],
+            padding,
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A character cursor that knows its byte offset in the line
+struct Cursor<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, chars: input.char_indices().peekable() }
+    }
+
+    /// Offset of the next character, or the end of the line
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |(i, _)| *i)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn next_if(&mut self, f: impl FnOnce(char) -> bool) -> Option<char> {
+        self.chars.next_if(|(_, c)| f(*c)).map(|(_, c)| c)
+    }
+
+    fn next_if_eq(&mut self, expected: char) -> bool {
+        self.next_if(|c| c == expected).is_some()
+    }
+}
+
+/// Split a command line into words and operators, handling quotes and escapes.
+///
+/// Quoting follows POSIX shells:
+/// - a backslash outside quotes takes the next character literally
+/// - single quotes take everything up to the closing quote literally
+/// - double quotes keep `$` expansion, and a backslash inside them only
+///   escapes `$`, `` ` ``, `"`, `\` and newline
+/// - `$'...'` takes its contents literally after decoding C-style escapes
+///
+/// An unquoted `#` at the start of a word begins a comment, which runs to the
+/// end of the line. An unquoted newline separates commands.
+///
+/// Quoted and unquoted segments with nothing between them form one word.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut current_arg = WordBuilder::default();
+    // where the open double quote is, while inside one
+    let mut open_quote: Option<usize> = None;
+    let mut chars = Cursor::new(input);
+
+    loop {
+        let start = chars.pos();
+        let Some(c) = chars.next() else { break };
+        let in_quotes = open_quote.is_some();
+
+        if c == '#' && !in_quotes && current_arg.start.is_none() {
+            while chars.next_if(|c| c != '\n').is_some() {}
+            continue;
+        }
+
+        if in_quotes || !(c.is_whitespace() || matches!(c, '|' | ';' | '&' | '<' | '>')) {
+            current_arg.begin(start);
+        }
+
+        if c == '\\' && in_quotes {
+            match chars.next_if(|next| matches!(next, '$' | '`' | '"' | '\\' | '\n')) {
+                Some('\n') => {}
+                Some(next) => current_arg.push_quoted(next),
+                None => current_arg.push_quoted(c),
+            }
+        } else if c == '\\' {
+            match chars.next() {
+                // a backslash-newline is a line continuation and disappears
+                Some('\n') => {}
+                Some(next) => {
+                    current_arg.push_quoted(next);
+                    current_arg.quoted = true;
+                }
+                None => return Err(ParseError::unfinished("trailing backslash", Span::new(start, start + 1))),
+            }
+        } else if c == '"' {
+            open_quote = if in_quotes { None } else { Some(start) };
+            current_arg.quoted = true;
+        } else if c == '$' && !in_quotes && chars.next_if_eq('\'') {
+            let text = read_ansi_c_quoted(&mut chars, start)?;
+            current_arg.push_quoted_str(&text);
+        } else if c == '$' {
+            match parse_param(&mut chars, start)? {
+                Some(name) if in_quotes => current_arg.push_part(WordPart::QuotedParam(name)),
+                Some(name) => current_arg.push_part(WordPart::Param(name)),
+                None if in_quotes => current_arg.push_quoted(c),
+                None => current_arg.push(c),
+            }
+        } else if in_quotes {
+            current_arg.push_quoted(c);
+        } else if c == '\'' {
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') => break,
+                    Some(c) => text.push(c),
+                    None => return Err(unclosed_quote('\'', start)),
+                }
+            }
+            current_arg.push_quoted_str(&text);
+        } else if c == '\n' {
+            current_arg.finish(&mut tokens, start);
+            tokens.push(Token { kind: TokenKind::Newline, span: Span::new(start, chars.pos()) });
+        } else if c.is_whitespace() {
+            current_arg.finish(&mut tokens, start);
+        } else if c == '|' {
+            current_arg.finish(&mut tokens, start);
+            let kind = if chars.next_if_eq('|') { TokenKind::Or } else { TokenKind::Pipe };
+            tokens.push(Token { kind, span: Span::new(start, chars.pos()) });
+        } else if c == ';' {
+            current_arg.finish(&mut tokens, start);
+            tokens.push(Token { kind: TokenKind::Semi, span: Span::new(start, chars.pos()) });
+        } else if c == '&' && chars.next_if_eq('&') {
+            current_arg.finish(&mut tokens, start);
+            tokens.push(Token { kind: TokenKind::And, span: Span::new(start, chars.pos()) });
+        } else if c == '<' || c == '>' {
+            // a bare number right before the operator names the file descriptor
+            let fd_start = current_arg.start.unwrap_or(start);
+            let fd = current_arg.take_fd();
+            if fd.is_none() {
+                current_arg.finish(&mut tokens, start);
+            }
+
+            let kind = if c == '<' {
+                if chars.next_if_eq('&') { RedirectKind::DuplicateInput } else { RedirectKind::Read }
+            } else if chars.next_if_eq('>') {
+                RedirectKind::Append
+            } else if chars.next_if_eq('&') {
+                RedirectKind::Duplicate
+            } else {
+                RedirectKind::Write
+            };
+            let span = Span::new(if fd.is_some() { fd_start } else { start }, chars.pos());
+            tokens.push(Token { kind: TokenKind::Redirect(fd, kind), span });
+        } else if c == '&' && chars.peek() == Some('>') {
+            current_arg.finish(&mut tokens, start);
+            chars.next();
+            let kind = if chars.next_if_eq('>') {
+                RedirectKind::AppendBoth
+            } else {
+                RedirectKind::WriteBoth
+            };
+            tokens.push(Token { kind: TokenKind::Redirect(None, kind), span: Span::new(start, chars.pos()) });
+        } else if c == '&' {
+            current_arg.finish(&mut tokens, start);
+            tokens.push(Token { kind: TokenKind::Background, span: Span::new(start, chars.pos()) });
+        } else if c == '=' && current_arg.can_start_assignment() {
+            current_arg.assign_name = Some(std::mem::take(&mut current_arg.literal));
+        } else {
+            current_arg.push(c);
+        }
+    }
+
+    if let Some(quote) = open_quote {
+        return Err(unclosed_quote('"', quote));
+    }
+
+    current_arg.finish(&mut tokens, input.len());
+
+    Ok(tokens)
+}
+
+fn unclosed_quote(quote: char, at: usize) -> ParseError {
+    ParseError::unfinished(
+        format!("unexpected EOF while looking for matching `{}'", quote),
+        Span::new(at, at + 1),
+    )
+}
+
+/// Read the body of a `$'...'` string up to its closing quote, decoding the
+/// backslash escapes. A decoded NUL ends the string, as in bash, since it
+/// cannot be passed on in an argument. `start` is where the `$` is
+fn read_ansi_c_quoted(chars: &mut Cursor, start: usize) -> Result<String, ParseError> {
+    let mut text = String::new();
+    loop {
+        let c = match chars.next() {
+            Some('\'') => {
+                if let Some(nul) = text.find('\0') {
+                    text.truncate(nul);
+                }
+                return Ok(text);
+            }
+            Some(c) => c,
+            None => return Err(unclosed_quote('\'', start)),
+        };
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        let Some(escape) = chars.next() else {
+            return Err(unclosed_quote('\'', start));
+        };
+        match escape {
+            'n' => text.push('\n'),
+            't' => text.push('\t'),
+            'r' => text.push('\r'),
+            'a' => text.push('\x07'),
+            'b' => text.push('\x08'),
+            'e' | 'E' => text.push('\x1b'),
+            'f' => text.push('\x0c'),
+            'v' => text.push('\x0b'),
+            '\\' | '\'' | '"' | '?' => text.push(escape),
+            '0'..='7' => {
+                // up to three octal digits, the first one already read
+                let mut value = escape.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match chars.next_if(|c| c.is_digit(8)) {
+                        Some(digit) => value = value * 8 + digit.to_digit(8).unwrap_or(0),
+                        None => break,
+                    }
+                }
+                text.extend(char::from_u32(value));
+            }
+            'x' | 'u' | 'U' => {
+                let max_digits = match escape {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let mut digits = String::new();
+                while digits.len() < max_digits {
+                    match chars.next_if(|c| c.is_ascii_hexdigit()) {
+                        Some(digit) => digits.push(digit),
+                        None => break,
+                    }
+                }
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(decoded) => text.push(decoded),
+                    // no digits: keep the escape as written
+                    None => {
+                        text.push('\\');
+                        text.push(escape);
+                        text.push_str(&digits);
+                    }
+                }
+            }
+            other => {
+                text.push('\\');
+                text.push(other);
+            }
+        }
+    }
+}
+
+/// Read the parameter name after the `$` at `start`, or None if the `$` is a
+/// plain character
+fn parse_param(chars: &mut Cursor, start: usize) -> Result<Option<String>, ParseError> {
+    if chars.next_if_eq('?') {
+        return Ok(Some("?".to_string()));
+    }
+
+    if chars.next_if_eq('{') {
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(c) => name.push(c),
+                None => return Err(unclosed_quote('}', start + 1)),
+            }
+        }
+        if name != "?" && !is_valid_name(&name) {
+            return Err(ParseError::new(
+                format!("${{{}}}: bad substitution", name),
+                Span::new(start, chars.pos()),
+            ));
+        }
+        return Ok(Some(name));
+    }
+
+    let mut name = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || c == '_') {
+        // names cannot start with a digit
+        if name.is_empty() && c.is_ascii_digit() {
+            return Ok(Some(c.to_string()));
+        }
+        name.push(c);
+    }
+    Ok(if name.is_empty() { None } else { Some(name) })
+}
+
+/// Accumulates the parts of the word currently being lexed
+#[derive(Default)]
+struct WordBuilder {
+    parts: Vec<WordPart>,
+    literal: String,
+    // whether `literal` holds quoted text
+    literal_quoted: bool,
+    // whether any part of the word was quoted or escaped
+    quoted: bool,
+    // set once an unquoted `NAME=` has been read at the start of the word
+    assign_name: Option<String>,
+    // where the word started in the line
+    start: Option<usize>,
+}
+
+impl WordBuilder {
+    /// Note that the word has reached `pos`, starting it if it is new
+    fn begin(&mut self, pos: usize) {
+        self.start.get_or_insert(pos);
+    }
+
+    fn push(&mut self, c: char) {
+        if self.literal_quoted {
+            self.flush_literal();
+        }
+        self.literal.push(c);
+    }
+
+    fn push_quoted(&mut self, c: char) {
+        if !self.literal_quoted {
+            self.flush_literal();
+            self.literal_quoted = true;
+        }
+        self.literal.push(c);
+    }
+
+    fn push_quoted_str(&mut self, text: &str) {
+        // an empty quoted string still makes a word, e.g. ''
+        self.quoted = true;
+        text.chars().for_each(|c| self.push_quoted(c));
+    }
+
+    fn push_part(&mut self, part: WordPart) {
+        self.flush_literal();
+        self.parts.push(part);
+    }
+
+    fn flush_literal(&mut self) {
+        if !self.literal.is_empty() {
+            let text = std::mem::take(&mut self.literal);
+            self.parts.push(if self.literal_quoted {
+                WordPart::Quoted(text)
+            } else {
+                WordPart::Literal(text)
+            });
+        }
+        self.literal_quoted = false;
+    }
+
+    /// Take the word as a file descriptor number if it is a bare, unquoted number
+    fn take_fd(&mut self) -> Option<i32> {
+        if self.quoted || !self.parts.is_empty() || self.literal.is_empty() {
+            return None;
+        }
+        if !self.literal.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let fd = self.literal.parse().ok()?;
+        self.literal.clear();
+        self.start = None;
+        Some(fd)
+    }
+
+    fn can_start_assignment(&self) -> bool {
+        !self.quoted && self.assign_name.is_none() && self.parts.is_empty() && is_valid_name(&self.literal)
+    }
+
+    /// Push the word, which ends at `end`, as a token if anything was
+    /// collected, and start a new one
+    fn finish(&mut self, tokens: &mut Vec<Token>, end: usize) {
+        self.flush_literal();
+        let span = Span::new(self.start.take().unwrap_or(end), end);
+        let word = Word(std::mem::take(&mut self.parts));
+        let kind = if let Some(name) = self.assign_name.take() {
+            Some(TokenKind::Assignment(name, word))
+        } else if !word.0.is_empty() {
+            Some(TokenKind::Word(word))
+        } else if self.quoted {
+            // only quotes, like "" or '', is an empty argument
+            Some(TokenKind::Word(Word(vec![WordPart::Quoted(String::new())])))
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            tokens.push(Token { kind, span });
+        }
+        self.quoted = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_tokens(input: &str) -> Vec<Word> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token.kind {
+                TokenKind::Word(word) => Some(word),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The words of a line as plain strings, with parameters written as `$NAME`
+    fn words(input: &str) -> Vec<String> {
+        word_tokens(input)
+            .into_iter()
+            .map(|word| {
+                word.0
+                    .into_iter()
+                    .map(|part| match part {
+                        WordPart::Literal(text) | WordPart::Quoted(text) => text,
+                        WordPart::Param(name) | WordPart::QuotedParam(name) => format!("${}", name),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_quotes_are_fully_literal() {
+        assert_eq!(words(r"echo 'it works' '$HOME \n'"), vec!["echo", "it works", r"$HOME \n"]);
+        assert_eq!(word_tokens("'$HOME'")[0], Word(vec![WordPart::Quoted("$HOME".to_string())]));
+    }
+
+    #[test]
+    fn double_quotes_keep_parameter_expansion() {
+        assert_eq!(
+            word_tokens(r#""hi $USER""#)[0],
+            Word(vec![
+                WordPart::Quoted("hi ".to_string()),
+                WordPart::QuotedParam("USER".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn backslash_in_double_quotes_only_escapes_special_characters() {
+        assert_eq!(words(r#""\a\$\"\\\`""#), vec![r#"\a$"\`"#]);
+    }
+
+    #[test]
+    fn backslash_outside_quotes_escapes_any_character() {
+        assert_eq!(words(r"a\ b \$x \*"), vec!["a b", "$x", "*"]);
+        assert_eq!(word_tokens(r"\*")[0], Word(vec![WordPart::Quoted("*".to_string())]));
+    }
+
+    #[test]
+    fn ansi_c_quotes_decode_escapes() {
+        assert_eq!(words(r"$'a\tb\nc'"), vec!["a\tb\nc"]);
+        assert_eq!(words(r"$'\x41\101é\e'"), vec!["AAé\x1b"]);
+        assert_eq!(words(r"$'it\'s' $'\q'"), vec!["it's", r"\q"]);
+    }
+
+    #[test]
+    fn ansi_c_quotes_end_at_a_nul() {
+        assert_eq!(words(r"$'a\x00b'x $'\0' $'c\u0000\'d'"), vec!["ax", "", "c"]);
+    }
+
+    #[test]
+    fn ansi_c_quotes_are_plain_inside_double_quotes() {
+        assert_eq!(words(r#""$'x'""#), vec!["$'x'"]);
+    }
+
+    #[test]
+    fn adjacent_quoted_segments_form_one_word() {
+        assert_eq!(words(r#"echo "a"'b'c$'\t'd"#), vec!["echo", "abc\td"]);
+    }
+
+    #[test]
+    fn empty_quotes_are_empty_arguments() {
+        assert_eq!(words(r#"echo "" '' $''"#), vec!["echo", "", "", ""]);
+    }
+
+    #[test]
+    fn backslash_newline_continues_the_line() {
+        assert_eq!(words("echo a\\\nb"), vec!["echo", "ab"]);
+        assert_eq!(words("\"a\\\nb\""), vec!["ab"]);
+    }
+
+    #[test]
+    fn operators_inside_quotes_are_literal() {
+        assert_eq!(words(r#"echo '|' ";" '&&' '>'"#), vec!["echo", "|", ";", "&&", ">"]);
+    }
+
+    #[test]
+    fn unclosed_quotes_are_errors() {
+        assert!(tokenize("echo 'abc").is_err());
+        assert!(tokenize("echo \"abc").is_err());
+        assert!(tokenize("echo $'abc").is_err());
+        assert!(tokenize("echo abc\\").is_err());
+    }
+
+    #[test]
+    fn tokens_record_their_span() {
+        let spans: Vec<Span> = tokenize("echo 'a b'>>out 2>&1").unwrap().into_iter().map(|t| t.span).collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(0, 4), Span::new(5, 10), Span::new(10, 12), Span::new(12, 15), Span::new(16, 19), Span::new(19, 20)]
+        );
+    }
+
+    #[test]
+    fn redirects_take_a_descriptor_prefix() {
+        let redirects: Vec<(Option<i32>, RedirectKind)> = tokenize("cat 3<&0 <in >&- 2>&1 x<&y")
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token.kind {
+                TokenKind::Redirect(fd, kind) => Some((fd, kind)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            redirects,
+            vec![
+                (Some(3), RedirectKind::DuplicateInput),
+                (None, RedirectKind::Read),
+                (None, RedirectKind::Duplicate),
+                (Some(2), RedirectKind::Duplicate),
+                (None, RedirectKind::DuplicateInput),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_start_at_a_word() {
+        let words: Vec<TokenKind> = tokenize("echo a#b '#c' # rest\nls;#x").unwrap().into_iter().map(|t| t.kind).collect();
+        assert_eq!(words.len(), 6);
+        assert_eq!(words[1], TokenKind::Word(Word(vec![WordPart::Literal("a#b".to_string())])));
+        // the comment ends at the newline, which ends the first command
+        assert_eq!(words[3], TokenKind::Newline);
+        assert_eq!(words[5], TokenKind::Semi);
+    }
+
+    #[test]
+    fn errors_point_at_the_opening_quote() {
+        let error = tokenize("echo \"abc").unwrap_err();
+        assert_eq!(error.span, Span::new(5, 6));
+        assert_eq!(
+            error.render("echo \"abc"),
+            "unexpected EOF while looking for matching `\"'\necho \"abc\n     ^"
+        );
+    }
+}
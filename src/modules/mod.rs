@@ -2,7 +2,24 @@ pub mod startup;
 pub mod shell;
 pub mod features;
 pub mod util;
+pub mod ast;
+pub mod lexer;
+pub mod parser;
+pub mod exec;
+pub mod redirect;
+pub mod expand;
+pub mod glob;
+pub mod path;
+pub mod completion;
+pub mod helper;
+pub mod highlight;
+pub mod prompt;
+pub mod git;
+pub mod git_objects;
+#[cfg(test)]
+pub mod test_util;
 
 pub use startup::boot;
 pub use features::{*};
-pub use util::{*};
\ No newline at end of file
+pub use util::{*};
+pub use parser::parse;
\ No newline at end of file
@@ -0,0 +1,282 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    ast::{CommandList, ListItem, ListOp, Pipeline, Redirect, RedirectKind, SimpleCommand, Word, WordPart},
+    lexer::{ParseError, Span, Token, TokenKind, tokenize},
+};
+
+/// Parse a command line into a list of pipelines joined by `;`, newlines, `&`,
+/// `&&` and `||`
+pub fn parse(input: &str) -> Result<CommandList, ParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        input,
+        tokens: tokens.into_iter().peekable(),
+        last_end: 0,
+    };
+    parser.parse_list()
+}
+
+/// Recursive descent over the token stream:
+///
+/// ```text
+/// list     := pipeline (( ';' | NEWLINE | '&' | '&&' | '||' ) pipeline)* [ ';' | NEWLINE | '&' ]
+/// pipeline := command ( '|' command )*
+/// command  := ( assignment | word | redirect )+
+/// redirect := REDIRECT word
+/// ```
+///
+/// Any number of newlines may come before a pipeline or after a `|`, so
+/// blank lines and commands continued after an operator are fine
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Peekable<IntoIter<Token>>,
+    // where the last token taken ends
+    last_end: usize,
+}
+
+impl Parser<'_> {
+    fn parse_list(&mut self) -> Result<CommandList, ParseError> {
+        let mut items = Vec::new();
+        let mut op = ListOp::Always;
+
+        // `&&` and `||` need a pipeline after them, `;` and `&` may end the line
+        loop {
+            self.skip_newlines();
+            if self.tokens.peek().is_none() && op == ListOp::Always {
+                break;
+            }
+            let pipeline = self.parse_pipeline()?;
+            let terminator = self.tokens.next();
+            let background = terminator.as_ref().is_some_and(|t| t.kind == TokenKind::Background);
+            items.push(ListItem { op, pipeline, background });
+
+            op = match terminator {
+                None => ListOp::Always,
+                Some(token) => match token.kind {
+                    TokenKind::Semi | TokenKind::Newline | TokenKind::Background => ListOp::Always,
+                    TokenKind::And => ListOp::IfSuccess,
+                    TokenKind::Or => ListOp::IfFailure,
+                    _ => return Err(unexpected(&token)),
+                },
+            };
+        }
+
+        Ok(CommandList { items })
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let start = self.tokens.peek().map_or(self.input.len(), |t| t.span.start);
+        let mut commands = vec![self.parse_command()?];
+        while self.next_if(|t| t.kind == TokenKind::Pipe).is_some() {
+            self.skip_newlines();
+            commands.push(self.parse_command()?);
+        }
+        let text = self.input[start..self.last_end].to_string();
+        Ok(Pipeline { commands, text })
+    }
+
+    fn parse_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+
+        while let Some(token) = self.next_if(|t| matches!(t.kind, TokenKind::Word(_) | TokenKind::Assignment(..) | TokenKind::Redirect(..)))
+        {
+            match token.kind {
+                TokenKind::Word(word) => command.args.push(word),
+                // assignments only count before the command name
+                TokenKind::Assignment(name, value) if command.args.is_empty() => {
+                    command.assignments.push((name, value))
+                }
+                TokenKind::Assignment(name, value) => command.args.push(assignment_word(name, value)),
+                TokenKind::Redirect(fd, kind) => {
+                    let target = self.parse_redirect_target()?;
+                    let default_fd = match kind {
+                        RedirectKind::Read | RedirectKind::DuplicateInput => 0,
+                        _ => 1,
+                    };
+                    command.redirects.push(Redirect {
+                        fd: fd.unwrap_or(default_fd),
+                        kind,
+                        target,
+                    });
+                }
+                _ => unreachable!("only command tokens are taken"),
+            }
+        }
+
+        if command.is_empty() {
+            return Err(self.unexpected_next());
+        }
+        Ok(command)
+    }
+
+    fn parse_redirect_target(&mut self) -> Result<Word, ParseError> {
+        match self.next_if(|_| true) {
+            None => Err(self.unexpected_newline()),
+            Some(token) => match token.kind {
+                TokenKind::Word(target) => Ok(target),
+                TokenKind::Assignment(name, value) => Ok(assignment_word(name, value)),
+                _ => Err(unexpected(&token)),
+            },
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.tokens.next_if(|t| t.kind == TokenKind::Newline).is_some() {}
+    }
+
+    /// Take the next token if it matches, keeping track of where it ends
+    fn next_if(&mut self, f: impl FnOnce(&Token) -> bool) -> Option<Token> {
+        let token = self.tokens.next_if(f)?;
+        self.last_end = token.span.end;
+        Some(token)
+    }
+
+    fn unexpected_next(&mut self) -> ParseError {
+        match self.tokens.peek() {
+            Some(token) => unexpected(token),
+            // only a `|`, `&&` or `||` can be waiting for a command at the end
+            None => ParseError { incomplete: true, ..self.unexpected_newline() },
+        }
+    }
+
+    fn unexpected_newline(&self) -> ParseError {
+        syntax_error("newline", Span::new(self.input.len(), self.input.len()))
+    }
+}
+
+/// Turn a `NAME=value` token back into an ordinary word
+fn assignment_word(name: String, value: Word) -> Word {
+    let mut parts = vec![WordPart::Literal(format!("{}=", name))];
+    parts.extend(value.0);
+    Word(parts)
+}
+
+fn unexpected(token: &Token) -> ParseError {
+    syntax_error(token_symbol(&token.kind), token.span)
+}
+
+fn token_symbol(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Word(_) | TokenKind::Assignment(..) => "word",
+        TokenKind::Pipe => "|",
+        TokenKind::And => "&&",
+        TokenKind::Or => "||",
+        TokenKind::Semi => ";",
+        TokenKind::Newline => "newline",
+        TokenKind::Background => "&",
+        TokenKind::Redirect(_, kind) => redirect_symbol(*kind),
+    }
+}
+
+fn redirect_symbol(kind: RedirectKind) -> &'static str {
+    match kind {
+        RedirectKind::Read => "<",
+        RedirectKind::Write => ">",
+        RedirectKind::Append => ">>",
+        RedirectKind::Duplicate => ">&",
+        RedirectKind::DuplicateInput => "<&",
+        RedirectKind::WriteBoth => "&>",
+        RedirectKind::AppendBoth => "&>>",
+    }
+}
+
+fn syntax_error(token: &str, span: Span) -> ParseError {
+    ParseError::new(format!("syntax error near unexpected token `{}'", token), span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lists_of_pipelines() {
+        let list = parse("a | b && c; d || e;").unwrap();
+        let shape: Vec<(ListOp, usize)> = list
+            .items
+            .iter()
+            .map(|item| (item.op, item.pipeline.commands.len()))
+            .collect();
+        assert_eq!(
+            shape,
+            vec![(ListOp::Always, 2), (ListOp::IfSuccess, 1), (ListOp::Always, 1), (ListOp::IfFailure, 1)]
+        );
+    }
+
+    #[test]
+    fn assignments_only_count_before_the_command() {
+        let list = parse("A=1 cmd B=2 >out").unwrap();
+        let command = &list.items[0].pipeline.commands[0];
+        assert_eq!(command.assignments.len(), 1);
+        assert_eq!(command.args.len(), 2);
+        assert_eq!(command.redirects[0].fd, 1);
+    }
+
+    #[test]
+    fn duplicating_and_closing_descriptors() {
+        let list = parse("cmd <&3 4<&- >&- 2>&1").unwrap();
+        let redirects: Vec<(i32, RedirectKind, &Word)> = list.items[0].pipeline.commands[0]
+            .redirects
+            .iter()
+            .map(|redirect| (redirect.fd, redirect.kind, &redirect.target))
+            .collect();
+        let word = |text: &str| Word(vec![WordPart::Literal(text.to_string())]);
+        assert_eq!(
+            redirects,
+            vec![
+                (0, RedirectKind::DuplicateInput, &word("3")),
+                (4, RedirectKind::DuplicateInput, &word("-")),
+                (1, RedirectKind::Duplicate, &word("-")),
+                (2, RedirectKind::Duplicate, &word("1")),
+            ]
+        );
+        assert_eq!(parse("cat <&").unwrap_err().message, "syntax error near unexpected token `newline'");
+        assert_eq!(parse("cat <& |").unwrap_err().message, "syntax error near unexpected token `|'");
+    }
+
+    #[test]
+    fn ampersand_runs_the_pipeline_in_the_background() {
+        let list = parse("sleep 1 | cat  & echo hi &").unwrap();
+        let items: Vec<(&str, bool)> = list
+            .items
+            .iter()
+            .map(|item| (item.pipeline.text.as_str(), item.background))
+            .collect();
+        assert_eq!(items, vec![("sleep 1 | cat", true), ("echo hi", true)]);
+        assert!(parse("a & && b").is_err());
+    }
+
+    #[test]
+    fn errors_point_at_the_unexpected_token() {
+        let line = "echo a | | b";
+        let error = parse(line).unwrap_err();
+        assert_eq!(error.span, Span::new(9, 10));
+        assert_eq!(error.render(line), "syntax error near unexpected token `|'\necho a | | b\n         ^");
+
+        let error = parse("ls &&").unwrap_err();
+        assert_eq!(error.message, "syntax error near unexpected token `newline'");
+        assert_eq!(error.span, Span::new(5, 5));
+    }
+
+    #[test]
+    fn unfinished_lines_are_incomplete() {
+        for line in ["ls |", "ls &&", "ls ||", "echo \"abc", "echo 'abc", "echo abc \\"] {
+            assert!(parse(line).unwrap_err().incomplete, "{}", line);
+        }
+        assert!(!parse("ls >").unwrap_err().incomplete);
+        assert!(!parse("| ls").unwrap_err().incomplete);
+        assert!(parse("echo \"a\nb\" |\ncat").is_ok());
+        assert!(parse("ls &&\n\n").unwrap_err().incomplete);
+    }
+
+    #[test]
+    fn newlines_separate_commands() {
+        let list = parse("\necho one\n\necho two;\nls |\n wc &&\n pwd\n").unwrap();
+        let items: Vec<(&str, ListOp)> = list.items.iter().map(|item| (item.pipeline.text.as_str(), item.op)).collect();
+        assert_eq!(
+            items,
+            vec![("echo one", ListOp::Always), ("echo two", ListOp::Always), ("ls |\n wc", ListOp::Always), ("pwd", ListOp::IfSuccess)]
+        );
+        assert!(parse("echo a;;\necho b").is_err());
+    }
+}
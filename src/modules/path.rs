@@ -0,0 +1,144 @@
+use std::{
+    ffi::{CStr, CString, OsStr},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+use crate::shell::Shell;
+
+impl Shell {
+    /// Turn a path given to a built-in into the absolute path it refers to:
+    /// `~` and `~/...` are under the home directory, `~user/...` under that
+    /// user's home, relative paths are taken from the shell's directory and
+    /// absolute paths are kept as they are. Names need not be valid UTF-8
+    pub fn resolve_path(&self, path: impl AsRef<OsStr>) -> PathBuf {
+        resolve(path.as_ref(), &self.abs_cwd, &self.home_dir)
+    }
+}
+
+/// `Shell::resolve_path` for a given working and home directory
+pub fn resolve(path: &OsStr, cwd: &str, home: &str) -> PathBuf {
+    if let Some(expanded) = expand_tilde(path, home) {
+        return expanded;
+    }
+    if path.as_bytes().starts_with(b"/") {
+        PathBuf::from(path)
+    } else {
+        PathBuf::from(cwd).join(path)
+    }
+}
+
+/// Expand a leading `~` or `~user`, or None if the path does not start with
+/// one or the user does not exist
+fn expand_tilde(path: &OsStr, home: &str) -> Option<PathBuf> {
+    let bytes = path.as_bytes().strip_prefix(b"~")?;
+    let (user, rest) = match bytes.iter().position(|b| *b == b'/') {
+        Some(slash) => (&bytes[..slash], &bytes[slash + 1..]),
+        None => (bytes, &b""[..]),
+    };
+
+    let mut expanded = if user.is_empty() {
+        PathBuf::from(home)
+    } else {
+        user_home(OsStr::from_bytes(user))?
+    };
+    if !rest.is_empty() {
+        expanded.push(OsStr::from_bytes(rest));
+    }
+    Some(expanded)
+}
+
+/// Look up a user's home directory in the password database
+pub fn user_home(user: &OsStr) -> Option<PathBuf> {
+    let name = CString::new(user.as_bytes()).ok()?;
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut found: *mut libc::passwd = std::ptr::null_mut();
+    let mut buffer = vec![0 as libc::c_char; 4096];
+
+    let res = unsafe {
+        libc::getpwnam_r(name.as_ptr(), &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut found)
+    };
+    if res != 0 || found.is_null() || entry.pw_dir.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr(entry.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+/// Resolve the `.` and `..` components of an absolute path and drop repeated
+/// and trailing slashes, without looking at the file system. `..` at the root
+/// stays at the root
+pub fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    fn resolved(path: &str) -> PathBuf {
+        resolve(OsStr::new(path), "/work/dir", "/home/me")
+    }
+
+    #[test]
+    fn absolute_paths_are_kept() {
+        assert_eq!(resolved("/etc/hosts"), PathBuf::from("/etc/hosts"));
+        assert_eq!(resolved("/"), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn relative_paths_start_at_the_working_directory() {
+        assert_eq!(resolved("file.txt"), PathBuf::from("/work/dir/file.txt"));
+        assert_eq!(resolved("./a/../b"), PathBuf::from("/work/dir/./a/../b"));
+    }
+
+    #[test]
+    fn tilde_is_the_home_directory() {
+        assert_eq!(resolved("~"), PathBuf::from("/home/me"));
+        assert_eq!(resolved("~/"), PathBuf::from("/home/me"));
+        assert_eq!(resolved("~/notes/todo"), PathBuf::from("/home/me/notes/todo"));
+        // only a leading tilde is special
+        assert_eq!(resolved("a/~/b"), PathBuf::from("/work/dir/a/~/b"));
+    }
+
+    #[test]
+    fn tilde_user_is_that_users_home() {
+        let root_home = user_home(OsStr::new("root")).expect("root has a home directory");
+        assert_eq!(resolved("~root"), root_home);
+        assert_eq!(resolved("~root/x"), root_home.join("x"));
+    }
+
+    #[test]
+    fn unknown_users_are_left_as_written() {
+        assert_eq!(resolved("~no-such-user-here/x"), PathBuf::from("/work/dir/~no-such-user-here/x"));
+    }
+
+    #[test]
+    fn non_utf8_names_are_kept_byte_for_byte() {
+        let name = OsString::from_vec(b"caf\xe9".to_vec());
+        let expected = OsString::from_vec(b"/work/dir/caf\xe9".to_vec());
+        assert_eq!(resolve(&name, "/work/dir", "/home/me"), PathBuf::from(expected));
+
+        let under_home = OsString::from_vec(b"~/\xff".to_vec());
+        assert_eq!(resolve(&under_home, "/work/dir", "/home/me").as_os_str().as_bytes(), b"/home/me/\xff");
+    }
+
+    #[test]
+    fn normalize_resolves_dot_and_dot_dot() {
+        assert_eq!(normalize("/a/b/../../c"), "/c");
+        assert_eq!(normalize("/a/./b/../c/"), "/a/c");
+        assert_eq!(normalize("//a//b///"), "/a/b");
+        assert_eq!(normalize("/../.."), "/");
+    }
+}
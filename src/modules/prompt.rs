@@ -0,0 +1,275 @@
+use std::ffi::CStr;
+
+use chrono::{DateTime, Local};
+
+use crate::{git::GitStatus, shell::Shell};
+
+/// The prompt used while PS1 is unset
+const DEFAULT_PS1: &str = "\\{bold}\\{blue}ru-shell\\{reset}:\\{bold}\\{green}\\w\\{reset}\\{yellow}\\g\\{reset}$ ";
+
+/// What the prompt escapes stand for at the time the prompt is drawn
+#[derive(Debug, Clone)]
+pub struct PromptContext {
+    pub user: String,
+    pub host: String,
+    pub cwd: String, // with the home directory shown as `~`
+    pub status: i32,
+    pub jobs: usize,
+    pub root: bool,
+    pub now: DateTime<Local>,
+    pub git: Option<GitStatus>, // only looked up when the template uses `\g`
+}
+
+/// The terminal code for a colour or style named in a `\{name}` escape
+fn style_code(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "reset" => "\x1b[0m",
+        "bold" => "\x1b[1m",
+        "dim" => "\x1b[2m",
+        "underline" => "\x1b[4m",
+        "black" => "\x1b[30m",
+        "red" => "\x1b[31m",
+        "green" => "\x1b[32m",
+        "yellow" => "\x1b[33m",
+        "blue" => "\x1b[34m",
+        "magenta" => "\x1b[35m",
+        "cyan" => "\x1b[36m",
+        "white" => "\x1b[37m",
+        "default" => "\x1b[39m",
+        _ => return None,
+    })
+}
+
+/// Expand the escapes of a PS1-style template:
+///
+/// - `\u` user, `\h` host up to the first dot, `\H` the whole host name
+/// - `\w` current directory, `\W` its last component
+/// - `\t` 24-hour time, `\T` 12-hour time, `\A` hours and minutes, `\d` date
+/// - `\?` exit status of the last command, `\j` number of jobs
+/// - `\$` `#` for root and `$` for everyone else
+/// - `\g` the git branch and state, like ` (main*)`, inside a repository
+/// - `\n` newline, `\e` escape, `\\` backslash
+/// - `\{name}` a colour (black, red, green, yellow, blue, magenta, cyan,
+///   white, default) or style (bold, dim, underline, reset)
+///
+/// bash's `\[` and `\]` are accepted and dropped, since escape sequences need
+/// no marking. Anything else is kept as it is written
+pub fn render(template: &str, ctx: &PromptContext) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match escape {
+            'u' => out.push_str(&ctx.user),
+            'h' => out.push_str(ctx.host.split('.').next().unwrap_or_default()),
+            'H' => out.push_str(&ctx.host),
+            'w' => out.push_str(&ctx.cwd),
+            'W' => out.push_str(basename(&ctx.cwd)),
+            't' => out.push_str(&ctx.now.format("%H:%M:%S").to_string()),
+            'T' => out.push_str(&ctx.now.format("%I:%M:%S").to_string()),
+            'A' => out.push_str(&ctx.now.format("%H:%M").to_string()),
+            'd' => out.push_str(&ctx.now.format("%a %b %d").to_string()),
+            '?' => out.push_str(&ctx.status.to_string()),
+            'j' => out.push_str(&ctx.jobs.to_string()),
+            '$' => out.push(if ctx.root { '#' } else { '$' }),
+            'g' => out.push_str(&ctx.git.as_ref().map(GitStatus::segment).unwrap_or_default()),
+            'n' => out.push('\n'),
+            'e' => out.push('\x1b'),
+            '\\' => out.push('\\'),
+            '[' | ']' => {}
+            '{' => {
+                let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                match style_code(&name) {
+                    Some(code) => out.push_str(code),
+                    None => out.push_str(&format!("\\{{{}}}", name)),
+                }
+            }
+            other => {
+                out.push('\\');
+                out.push(other);
+            }
+        }
+    }
+    out
+}
+
+/// The last component of a prompt directory, keeping `~` and `/` whole
+fn basename(cwd: &str) -> &str {
+    match cwd.trim_end_matches('/').rsplit_once('/') {
+        Some((_, name)) if !name.is_empty() => name,
+        _ => cwd,
+    }
+}
+
+/// How many columns text takes on screen, leaving out escape sequences
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // a CSI sequence runs to its final letter, others are two characters
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else if !c.is_control() {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// RPS1, drawn against the right edge of the terminal on the last line of
+/// the prompt while the typed text leaves room for it
+#[derive(Debug, Clone)]
+pub struct RightPrompt {
+    text: String,
+    width: usize,
+    left_width: usize, // the width of the last line of PS1
+}
+
+impl RightPrompt {
+    /// The codes that draw the right prompt from the end of the typed text
+    /// and come back, or None when it would not fit beside that text
+    pub fn draw(&self, typed_width: usize, columns: usize) -> Option<String> {
+        // keep a space between the text and the prompt
+        if self.left_width + typed_width + 1 + self.width > columns {
+            return None;
+        }
+        let column = columns - self.width + 1;
+        Some(format!("\x1b[s\x1b[{}G{}\x1b[u", column, self.text))
+    }
+}
+
+pub fn terminal_columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res == 0 && size.ws_col > 0 { size.ws_col as usize } else { 80 }
+}
+
+fn host_name() -> String {
+    let mut buffer = [0 as libc::c_char; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().into_owned()
+}
+
+fn user_name() -> String {
+    let entry = unsafe { libc::getpwuid(libc::geteuid()) };
+    if entry.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr((*entry).pw_name) }.to_string_lossy().into_owned()
+}
+
+impl Shell {
+    fn prompt_context(&mut self, templates: &[&str]) -> PromptContext {
+        let wants_git = templates.iter().any(|template| template.contains("\\g"));
+        PromptContext {
+            user: self.get_var("USER").map_or_else(user_name, str::to_string),
+            host: host_name(),
+            cwd: self.current_dir.clone(),
+            status: self.last_status,
+            jobs: self.jobs.len(),
+            root: unsafe { libc::geteuid() } == 0,
+            now: Local::now(),
+            git: if wants_git { self.git_status() } else { None },
+        }
+    }
+
+    /// PS1 expanded for this moment, with RPS1 when it is set. The right
+    /// prompt is left out of the string handed to the line editor, which
+    /// would count it in the width of the prompt, and is drawn with the line
+    pub fn get_prompt(&mut self) -> (String, Option<RightPrompt>) {
+        let left_template = self.get_var("PS1").unwrap_or(DEFAULT_PS1).to_string();
+        let right_template = self.get_var("RPS1").map(str::to_string);
+        let ctx = self.prompt_context(&[&left_template, right_template.as_deref().unwrap_or_default()]);
+        let prompt = render(&left_template, &ctx);
+
+        let right = right_template.map(|template| render(&template, &ctx)).filter(|right| display_width(right) > 0);
+        let right_prompt = right.map(|text| RightPrompt {
+            width: display_width(&text),
+            left_width: display_width(prompt.rsplit('\n').next().unwrap_or_default()),
+            text,
+        });
+        (prompt, right_prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn context() -> PromptContext {
+        PromptContext {
+            user: "ada".to_string(),
+            host: "box.example.com".to_string(),
+            cwd: "~/src/shell".to_string(),
+            status: 127,
+            jobs: 2,
+            root: false,
+            now: Local.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).unwrap(),
+            git: None,
+        }
+    }
+
+    #[test]
+    fn expands_escapes() {
+        let ctx = context();
+        assert_eq!(render("\\u@\\h:\\w\\$ ", &ctx), "ada@box:~/src/shell$ ");
+        assert_eq!(render("\\H \\W [\\?] \\j", &ctx), "box.example.com shell [127] 2");
+        assert_eq!(render("\\t \\A \\T", &ctx), "14:07:09 14:07 02:07:09");
+        assert_eq!(render("\\$", &PromptContext { root: true, ..ctx }), "#");
+    }
+
+    #[test]
+    fn basename_keeps_home_and_root() {
+        let ctx = context();
+        assert_eq!(render("\\W", &PromptContext { cwd: "~".to_string(), ..ctx.clone() }), "~");
+        assert_eq!(render("\\W", &PromptContext { cwd: "/".to_string(), ..ctx }), "/");
+    }
+
+    #[test]
+    fn colours_and_unknown_escapes() {
+        let ctx = context();
+        assert_eq!(render("\\{red}x\\{reset}", &ctx), "\x1b[31mx\x1b[0m");
+        assert_eq!(render("\\[\\e[1m\\]", &ctx), "\x1b[1m");
+        assert_eq!(render("\\{nope}\\q\\", &ctx), "\\{nope}\\q\\");
+    }
+
+    #[test]
+    fn git_segment_only_inside_a_repository() {
+        let ctx = context();
+        assert_eq!(render("\\W\\g$", &ctx), "shell$");
+        let git = GitStatus { head: "main".to_string(), detached: false, dirty: true, ahead: 2, behind: 1 };
+        assert_eq!(render("\\W\\g$", &PromptContext { git: Some(git.clone()), ..ctx.clone() }), "shell (main* ↑2↓1)$");
+        let git = GitStatus { dirty: false, behind: 0, ..git };
+        assert_eq!(render("\\W\\g$", &PromptContext { git: Some(git), ..ctx }), "shell (main ↑2)$");
+    }
+
+    #[test]
+    fn right_prompt_only_beside_short_lines() {
+        let right = RightPrompt { text: "\x1b[2m14:07\x1b[0m".to_string(), width: 5, left_width: 10 };
+        assert_eq!(right.draw(0, 40).unwrap(), "\x1b[s\x1b[36G\x1b[2m14:07\x1b[0m\x1b[u");
+        assert!(right.draw(24, 40).is_some());
+        assert!(right.draw(25, 40).is_none());
+    }
+
+    #[test]
+    fn width_leaves_out_escape_sequences() {
+        assert_eq!(display_width("\x1b[1;34mru\x1b[0m:~$ "), 6);
+        assert_eq!(display_width(&render(DEFAULT_PS1, &context())), "ru-shell:~/src/shell$ ".len());
+    }
+}
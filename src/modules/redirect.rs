@@ -0,0 +1,121 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, IntoRawFd},
+};
+
+use crate::{
+    ast::{Redirect, RedirectKind},
+    shell::Shell,
+};
+
+impl Shell {
+    /// Point file descriptors of the current process at the redirection targets,
+    /// in the order they were written
+    pub fn apply_redirects(&self, redirects: &[Redirect]) -> Result<(), String> {
+        for redirect in redirects {
+            let target = self.expand_word(&redirect.target);
+            let result = match redirect.kind {
+                // `-` closes the descriptor instead
+                RedirectKind::Duplicate | RedirectKind::DuplicateInput if target == "-" => {
+                    unsafe { libc::close(redirect.fd) };
+                    Ok(())
+                }
+                RedirectKind::Duplicate | RedirectKind::DuplicateInput => match target.parse() {
+                    Ok(source_fd) => dup_onto(source_fd, redirect.fd),
+                    Err(_) => Err(io::Error::other("ambiguous redirect")),
+                },
+                RedirectKind::WriteBoth | RedirectKind::AppendBoth => self
+                    .open_target(&target, redirect.kind)
+                    .and_then(|file| {
+                        dup_onto(file.as_raw_fd(), libc::STDERR_FILENO)?;
+                        move_onto(file, libc::STDOUT_FILENO)
+                    }),
+                _ => self
+                    .open_target(&target, redirect.kind)
+                    .and_then(|file| move_onto(file, redirect.fd)),
+            };
+
+            if let Err(e) = result {
+                return Err(format!("{}: {}", target, e));
+            }
+        }
+        Ok(())
+    }
+
+    fn open_target(&self, target: &str, kind: RedirectKind) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        match kind {
+            RedirectKind::Read => options.read(true),
+            RedirectKind::Append | RedirectKind::AppendBoth => options.append(true).create(true),
+            _ => options.write(true).create(true).truncate(true),
+        };
+        options.open(self.resolve_path(target))
+    }
+}
+
+fn dup_onto(source_fd: i32, target_fd: i32) -> io::Result<()> {
+    if unsafe { libc::dup2(source_fd, target_fd) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Put an opened file at a descriptor. The file may already have been given
+/// that descriptor when it was free, as with `3<file`, and is then kept open
+/// as it is, without the close-on-exec flag it was opened with
+fn move_onto(file: File, target_fd: i32) -> io::Result<()> {
+    if file.as_raw_fd() != target_fd {
+        return dup_onto(file.as_raw_fd(), target_fd);
+    }
+    let fd = file.into_raw_fd();
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Copies of the file descriptors a command's redirections will replace, so a
+/// built-in running inside the shell process can have them put back afterwards
+pub struct SavedFds {
+    saved: Vec<(i32, i32)>,
+}
+
+impl SavedFds {
+    pub fn save(redirects: &[Redirect]) -> Self {
+        let mut targets = Vec::new();
+        for redirect in redirects {
+            match redirect.kind {
+                RedirectKind::WriteBoth | RedirectKind::AppendBoth => {
+                    targets.push(libc::STDOUT_FILENO);
+                    targets.push(libc::STDERR_FILENO);
+                }
+                _ => targets.push(redirect.fd),
+            }
+        }
+        targets.sort();
+        targets.dedup();
+
+        // keep the copies above the low descriptors and out of child processes
+        let saved = targets
+            .into_iter()
+            .map(|fd| (fd, unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) }))
+            .collect();
+
+        Self { saved }
+    }
+
+    pub fn restore(self) {
+        for (fd, copy) in self.saved {
+            unsafe {
+                if copy == -1 {
+                    // the descriptor was not open before the redirection
+                    libc::close(fd);
+                } else {
+                    libc::dup2(copy, fd);
+                    libc::close(copy);
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,47 @@
 use libc::{STDOUT_FILENO, write};
 use std::ffi::CString;
-use std::{env, fs::{File, OpenOptions}, io::{self, BufRead, BufReader, Write}, path::PathBuf};
+use std::{collections::HashMap, env, fs::{File, OpenOptions}, io::{self, BufRead, BufReader, Write}, path::PathBuf};
 
-use super::echo::echoln;
+use super::complete::CompletionSpec;
+use super::git::GitCache;
+use super::echo::{echoln, echoln_err};
+use super::jobs::JobTable;
+use super::shopt::ShellOptions;
+use super::variables::{Variable, variables_from_env};
+
+/// A command from the history, with the directory it was run in when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub cwd: Option<String>,
+}
+
+/// Starts the history file line naming the directory of the command after it
+const HISTORY_CWD_PREFIX: &str = "#cwd ";
 
 #[allow(dead_code)]
 pub struct Shell {
-    pub history: Vec<String>,
+    pub history: Vec<HistoryEntry>,
     pub home_dir: String,
     pub current_dir: String,    // represents the current path that will be used for stdout
     pub abs_cwd: String,        // represents the absolute path to the current working directory
+    pub should_exit: bool,      // set by the exit built-in to end the session
+    pub last_status: i32,       // exit status of the last command, exposed as $?
+    pub variables: HashMap<String, Variable>,
+    pub options: ShellOptions,
+    pub jobs: JobTable,
+    pub dir_stack: Vec<String>, // pushd directories below the current one, most recent first
+    pub interactive: bool,      // whether job control is on, set up by init_job_control
+    pub terminal: i32,          // the shell's own descriptor for the terminal, -1 without job control
+    pub completions: HashMap<String, CompletionSpec>, // registered with the complete built-in
+    pub git_cache: GitCache,    // git status for the prompt, by work tree
+    pub source_location: Option<(String, usize)>, // file and line of the startup file command running
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Shell {
@@ -32,8 +64,23 @@ impl Shell {
             home_dir,
             current_dir: relative_cwd,
             abs_cwd,
+            should_exit: false,
+            last_status: 0,
+            variables: variables_from_env(),
+            options: ShellOptions::default(),
+            jobs: JobTable::default(),
+            dir_stack: Vec::new(),
+            interactive: false,
+            terminal: -1,
+            completions: HashMap::new(),
+            git_cache: GitCache::new(),
+            source_location: None,
         };
 
+        // PWD always names the directory the shell is in
+        let cwd = res.abs_cwd.clone();
+        res.export_var("PWD", &cwd);
+
         res.load_history().unwrap_or_else(|e| {
             echoln(&format!("Error loading history: {}", e));
         });
@@ -41,11 +88,6 @@ impl Shell {
         res
     }
 
-    pub fn get_prompt(&self) -> String {
-        // printing colors of prompt as inverse of pop-os terminal :)
-        format!("\x1b[1;34mru-shell\x1b[0m:\x1b[1;32m{:#}\x1b[0m$ ", self.current_dir)        
-    }
-
     pub fn history_file_path(&self) -> PathBuf {
         PathBuf::from(&self.home_dir).join(".rushistory")
     }
@@ -62,13 +104,24 @@ impl Shell {
         let file = File::open(history_path)?;
         let reader = BufReader::new(file);
         
-        for line in reader.lines() {
-            if let Ok(command) = line {
-                if !command.trim().is_empty() {
-                    self.history.push(command);
+        // an entry after a directory line runs until the next one, so commands
+        // spanning several lines come back whole. Entries written before
+        // directories were recorded are one line each
+        let mut entry: Option<HistoryEntry> = None;
+        for line in reader.lines().map_while(Result::ok) {
+            if let Some(dir) = line.strip_prefix(HISTORY_CWD_PREFIX) {
+                self.history.extend(entry.take().filter(|e| !e.command.is_empty()));
+                entry = Some(HistoryEntry { command: String::new(), cwd: Some(dir.to_string()) });
+            } else if let Some(entry) = &mut entry {
+                if !entry.command.is_empty() {
+                    entry.command.push('\n');
                 }
+                entry.command.push_str(&line);
+            } else if !line.trim().is_empty() {
+                self.history.push(HistoryEntry { command: line, cwd: None });
             }
         }
+        self.history.extend(entry.filter(|e| !e.command.is_empty()));
         
         Ok(())
     }
@@ -78,22 +131,40 @@ impl Shell {
             return;
         }
         
-        self.history.push(command.clone());
-        
         // append to history file
         if let Ok(mut file) = OpenOptions::new()
             .create(true)
             .append(true)
             .open(self.history_file_path())
         {
-            let _ = writeln!(file, "{}", command);
+            let _ = writeln!(file, "{}{}\n{}", HISTORY_CWD_PREFIX, self.abs_cwd, command);
         }
+
+        let cwd = Some(self.abs_cwd.clone());
+        self.history.push(HistoryEntry { command, cwd });
     }
 
-    pub fn get_history(&self) -> &Vec<String> {
+    pub fn get_history(&self) -> &[HistoryEntry] {
         &self.history
     }
 
+    /// The rest of the newest history entry that starts with `line`, taking
+    /// one run in the current directory over any other. Commands spanning
+    /// several lines are not suggested, since a hint is drawn on one line
+    pub fn history_hint(&self, line: &str) -> Option<String> {
+        let mut fallback = None;
+        for entry in self.history.iter().rev() {
+            if entry.command.len() <= line.len() || !entry.command.starts_with(line) || entry.command.contains('\n') {
+                continue;
+            }
+            if entry.cwd.as_deref() == Some(self.abs_cwd.as_str()) {
+                return Some(entry.command[line.len()..].to_string());
+            }
+            fallback.get_or_insert(entry);
+        }
+        fallback.map(|entry| entry.command[line.len()..].to_string())
+    }
+
     pub fn clear(&self) {
         let clear_screen = CString::new("\x1B[2J\x1B[H").expect("CString::new failed");
         unsafe {
@@ -105,12 +176,35 @@ impl Shell {
         }
     }
 
+    pub fn handle_exit_command(&mut self, args: Vec<String>) -> i32 {
+        if args.len() > 1 {
+            self.error("exit: too many arguments", true);
+            return 1;
+        }
+
+        self.should_exit = true;
+        match args.first() {
+            // like other shells, only the low 8 bits reach the parent
+            Some(arg) => match arg.parse::<i64>() {
+                Ok(code) => (code & 0xff) as i32,
+                Err(_) => {
+                    self.error(&format!("exit: {}: numeric argument required", arg), true);
+                    2
+                }
+            },
+            None => self.last_status,
+        }
+    }
+
     pub fn error(&self, msg: &str, show_name: bool) {
-        let message = if show_name {
+        // errors from a startup file say where in it they happened
+        let message = if let Some((file, line)) = &self.source_location {
+            format!("{}: line {}: {}", file, line, msg)
+        } else if show_name {
             format!("ru-shell: {}", msg)
         } else {
             msg.to_string()
         };
-        echoln(message.as_str());
+        echoln_err(message.as_str());
     }
 }
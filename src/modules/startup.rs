@@ -1,69 +1,134 @@
-use std::io::{self};
-use rustyline::Editor;
+use std::{cell::RefCell, io::{self}, path::PathBuf, rc::Rc};
+use rustyline::{
+    CompletionType, Config, Editor, EventHandler, KeyCode, KeyEvent, Modifiers, error::ReadlineError, history::DefaultHistory,
+};
 
-use super::{echo::echoln, parse_args, shell::Shell};
+use super::{echo::{echoln, echoln_err}, helper::{AcceptHint, AcceptHintWord, ShellHelper}, prompt::RightPrompt, shell::Shell, parse};
 
-pub fn boot() -> io::Result<()> {
+const USAGE: &str = "usage: ru-shell [-l|--login] [--norc] [--rcfile file]";
 
-    let mut rl = Editor::<(), _>::new().unwrap();
-    let mut inst = Shell::new();
-    // load history if it exists
-    if let Err(e) = rl.load_history(&inst.history_file_path()) {
-        echoln(&format!("Error loading history: {}", e));
+/// Command-line options of the shell
+#[derive(Debug, Clone, Default)]
+pub struct StartupOptions {
+    pub login: bool,             // read ~/.rush_profile, set by -l, --login or a leading `-` in argv[0]
+    pub norc: bool,              // skip the rc file
+    pub rcfile: Option<PathBuf>, // read this instead of ~/.rushrc
+}
+
+impl StartupOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            // login programs start a login shell with a name like `-ru-shell`
+            login: args.next().is_some_and(|name| name.starts_with('-')),
+            ..Self::default()
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-l" | "--login" => options.login = true,
+                "--norc" => options.norc = true,
+                "--rcfile" => match args.next() {
+                    Some(path) => options.rcfile = Some(PathBuf::from(path)),
+                    None => return Err("--rcfile: option requires an argument".to_string()),
+                },
+                _ => return Err(format!("{}: invalid option", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl Shell {
+    /// Read the startup files: `~/.rush_profile` for a login shell, then the
+    /// rc file for an interactive one. Missing default files are skipped, a
+    /// missing `--rcfile` is reported
+    fn run_startup_files(&mut self, options: &StartupOptions) {
+        if options.login {
+            let profile = PathBuf::from(&self.home_dir).join(".rush_profile");
+            if profile.is_file() && let Err(e) = self.source_file(&profile) {
+                self.error(&format!("{}: {}", profile.display(), e), true);
+            }
+        }
+        if !self.interactive || options.norc || self.should_exit {
+            return;
+        }
+
+        let rcfile = match &options.rcfile {
+            Some(path) => self.resolve_path(path),
+            None => {
+                let default = PathBuf::from(&self.home_dir).join(".rushrc");
+                if !default.is_file() {
+                    return;
+                }
+                default
+            }
+        };
+        if let Err(e) = self.source_file(&rcfile) {
+            self.error(&format!("{}: {}", rcfile.display(), e), true);
+        }
     }
+}
 
-    loop {
-        match rl.readline(inst.get_prompt().as_str()) {
+pub fn boot() -> io::Result<i32> {
+    let options = match StartupOptions::from_args(std::env::args()) {
+        Ok(options) => options,
+        Err(e) => {
+            echoln_err(&format!("ru-shell: {}", e));
+            echoln_err(USAGE);
+            return Ok(2);
+        }
+    };
+
+    // list every candidate when completion is ambiguous, like bash
+    let config = Config::builder().completion_type(CompletionType::List).build();
+    let mut rl = Editor::<ShellHelper, DefaultHistory>::with_config(config).unwrap();
+    let shell = Rc::new(RefCell::new(Shell::new()));
+    rl.set_helper(Some(ShellHelper::new(Rc::clone(&shell))));
+    // Right already accepts a suggestion, End and Alt-F are added here
+    rl.bind_sequence(KeyEvent(KeyCode::End, Modifiers::NONE), EventHandler::Conditional(Box::new(AcceptHint)));
+    rl.bind_sequence(KeyEvent::alt('f'), EventHandler::Conditional(Box::new(AcceptHintWord)));
+    shell.borrow_mut().init_job_control();
+    // the editor's history is the shell's, which also knows where each command ran
+    for entry in shell.borrow().get_history() {
+        let _ = rl.add_history_entry(entry.command.as_str());
+    }
+    shell.borrow_mut().run_startup_files(&options);
+
+    while !shell.borrow().should_exit {
+        let (prompt, right_prompt) = {
+            let mut inst = shell.borrow_mut();
+            inst.notify_jobs();
+            inst.get_prompt()
+        };
+        // the helper reads the shell while the line is edited
+        let readline = read_command(&mut rl, &shell, &prompt, right_prompt);
+        let mut inst = shell.borrow_mut();
+        match readline {
             Ok(line) => {
                 if line.trim().is_empty() {
                     continue;
                 }
                 let _ = rl.add_history_entry(line.clone());
                 inst.add_to_history(line.clone());
-                // splitting the line into command and arguments
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                let command : &str = parts[0];
-                let args = match parse_args(&line[command.len()..]) {
-                    Ok(parsed_args) => parsed_args,
+                let list = match parse(&line) {
+                    Ok(list) => list,
                     Err(e) => {
-                        inst.error(&e.to_string(), true);
+                        inst.error(&e.render(&line), true);
+                        inst.last_status = 2;
                         continue;
                     }
                 };
 
-
-                match command {
-                    "exit" => {
-                        break;
-                    }
-                   "echo" => Shell::handle_echo_command(args),
-                    "cd" => {
-                        if args.len() > 1 {
-                            inst.error("cd: too many arguments", true);
-                            continue;
-                        }
-                        let path = if !args.is_empty() { args[0].trim() } else { "" };
-                        let res = inst.cd(path);
-                        if let Err(e) = res {
-                            echoln(e.as_str());
-                        }
-                    }
-                    "pwd" => inst.pwd(),
-                    "ls" => inst.handle_ls_command(args),
-                    "cat" => inst.handle_cat_command(args),
-                    "cp" => inst.handle_copy_command(args),
-                    "mv" => inst.handle_move_command(args),
-                    "mkdir" => inst.handle_mkdir_command(args),
-                    "rm" => inst.handle_rm_command(args),
-                    "clear" => inst.clear(),
-                    _ => inst.error(format!("{}: command not found", line.trim()).as_str(), false),
+                inst.run_list(list);
+                if inst.should_exit {
+                    break;
                 }
             }
-            Err(rustyline::error::ReadlineError::Interrupted) => {
-                echoln("\nCtrl-C pressed, exiting...");
-                break;
+            Err(ReadlineError::Interrupted) => {
+                // drop the line being typed and start over on a fresh prompt
+                inst.last_status = 130;
+                continue;
             }
-            Err(rustyline::error::ReadlineError::Eof) => {
+            Err(ReadlineError::Eof) => {
                 echoln("\nEOF received, exiting...");
                 break;
             }
@@ -73,5 +138,39 @@ pub fn boot() -> io::Result<()> {
             }
         }
     }
-    Ok(())
+    let status = shell.borrow().last_status;
+    Ok(status)
+}
+
+/// Read a command, prompting with PS2 for more lines while it is unfinished:
+/// inside quotes, after a trailing backslash or after a `|`, `&&` or `||`.
+/// End of file in the middle returns what was read, so that the error about
+/// what is left open gets reported. The right prompt is only drawn on the
+/// first line
+fn read_command(
+    rl: &mut Editor<ShellHelper, DefaultHistory>,
+    shell: &Rc<RefCell<Shell>>,
+    prompt: &str,
+    right_prompt: Option<RightPrompt>,
+) -> rustyline::Result<String> {
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_right_prompt(right_prompt);
+    }
+    let line = rl.readline(prompt);
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_right_prompt(None);
+    }
+    let mut line = line?;
+    while parse(&line).is_err_and(|e| e.incomplete) {
+        let ps2 = shell.borrow().get_var("PS2").unwrap_or("> ").to_string();
+        match rl.readline(&ps2) {
+            Ok(more) => {
+                line.push('\n');
+                line.push_str(&more);
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(line)
 }
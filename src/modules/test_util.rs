@@ -0,0 +1,27 @@
+use std::{fs, path::PathBuf};
+
+/// A scratch directory for a test, removed when dropped
+pub struct Fixture(pub PathBuf);
+
+impl Fixture {
+    /// An empty directory under the system's temporary one. Tests run in
+    /// parallel, so each one needs a name of its own
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("ru-shell-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        Self(root)
+    }
+
+    /// Write a file, creating the directories leading to it
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,45 +1,36 @@
-use std::io::Error;
-
-/// Parse command line arguments
-pub fn parse_args(input: &str) -> Result<Vec<String>, Error> {
-    if input.trim().is_empty() {
-        return Ok(Vec::new());
+/// Whether a string can be used as a variable name
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
     }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
 
-    let mut args = Vec::new();
-    let mut current_arg = String::new();
-    let mut in_quotes = false;
-    let mut escaped = false;
+/// The number of single-character insertions, deletions, substitutions and
+/// swaps of neighbouring characters needed to turn one string into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    distances[0] = (0..=b.len()).collect();
 
-    for c in input.chars() {
-        if escaped {
-            current_arg.push(c);
-            escaped = false;
-        } else if c == '\\' {
-            escaped = true;
-        } else if c == '"' {
-            in_quotes = !in_quotes;
-        } else if c.is_whitespace() && !in_quotes {
-            if !current_arg.is_empty() {
-                args.push(current_arg);
-                current_arg = String::new();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(distances[i - 2][j - 2] + 1);
             }
-        } else {
-            current_arg.push(c);
+            distances[i][j] = best;
         }
     }
-
-    if in_quotes {
-        return Err(Error::new(std::io::ErrorKind::InvalidInput, "unclosed quotes"));
-    }
-
-    if escaped {
-        return Err(Error::new(std::io::ErrorKind::InvalidInput, "trailing backslash"));
-    }
-
-    if !current_arg.is_empty() {
-        args.push(current_arg);
-    }
-
-    Ok(args)
+    distances[a.len()][b.len()]
 }
@@ -0,0 +1,78 @@
+use std::{
+    ffi::CStr,
+    fs::File,
+    io::{Read, Write},
+    os::{fd::FromRawFd, unix::process::CommandExt},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A pseudo-terminal: the master side, and the path of the slave side
+fn open_pty() -> (File, String) {
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(master >= 0, "posix_openpt failed");
+        assert_eq!(libc::grantpt(master), 0);
+        assert_eq!(libc::unlockpt(master), 0);
+        let slave = CStr::from_ptr(libc::ptsname(master)).to_string_lossy().into_owned();
+        (File::from_raw_fd(master), slave)
+    }
+}
+
+/// Run the shell on a terminal of its own, typing each line after a pause,
+/// and return everything it printed
+fn run_on_terminal(input: &[&str]) -> String {
+    let (mut master, slave) = open_pty();
+    let home = std::env::temp_dir().join(format!("ru-shell-job-control-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+
+    let tty = || File::options().read(true).write(true).open(&slave).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ru-shell"));
+    command
+        .arg("--norc")
+        .env("HOME", &home)
+        .env("TERM", "xterm")
+        .stdin(Stdio::from(tty()))
+        .stdout(Stdio::from(tty()))
+        .stderr(Stdio::from(tty()));
+    // a session of its own, with the pty as its controlling terminal
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            libc::ioctl(0, libc::TIOCSCTTY, 0);
+            Ok(())
+        });
+    }
+    let mut child = command.spawn().unwrap();
+    // only the shell may hold the slave open, or reading never ends
+    drop(command);
+
+    let mut reader = master.try_clone().unwrap();
+    let output = thread::spawn(move || {
+        let mut output = Vec::new();
+        let mut buffer = [0; 4096];
+        // ends with an error once the shell has exited and the slave is closed
+        while let Ok(n @ 1..) = reader.read(&mut buffer) {
+            output.extend_from_slice(&buffer[..n]);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    });
+
+    for line in input {
+        thread::sleep(Duration::from_millis(500));
+        master.write_all(line.as_bytes()).unwrap();
+    }
+    child.wait().unwrap();
+    drop(master);
+    let _ = std::fs::remove_dir_all(&home);
+    output.join().unwrap()
+}
+
+#[test]
+fn ctrl_c_reaches_a_job_with_redirected_stdin() {
+    let start = Instant::now();
+    let output = run_on_terminal(&["sleep 5 < /dev/null; echo status=$?\r", "\x03", "exit\r"]);
+    assert!(output.contains("status=130"), "{:?}", output);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}